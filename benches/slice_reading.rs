@@ -0,0 +1,74 @@
+//! Compares reading every frame of the bundled slice file with
+//! [`SliceParser`] and with [`MmapSlice`]. Run with `cargo bench`.
+use red_smv::{MmapSlice, SliceParser};
+use std::fs::File;
+use std::io::BufReader;
+use std::time::{Duration, Instant};
+
+const SLICE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/room_fire_01.sf");
+const ITERATIONS: u32 = 200;
+
+/// Time `f` over a number of iterations and print the mean time per
+/// iteration. The sum of the values read is returned by `f` so the work is
+/// not optimised away.
+fn bench<F: FnMut() -> f64>(name: &str, mut f: F) {
+    let mut total = 0.0;
+    // Warm the page cache and the mapping.
+    total += f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        total += f();
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{:<28} {:>10.1} µs/iter (checksum {})",
+        name,
+        per_iteration(elapsed),
+        total
+    );
+}
+
+fn per_iteration(elapsed: Duration) -> f64 {
+    elapsed.as_secs_f64() * 1e6 / ITERATIONS as f64
+}
+
+fn sum(values: &[f32]) -> f64 {
+    values.iter().map(|v| *v as f64).sum()
+}
+
+fn main() {
+    bench("SliceParser", || {
+        let file = BufReader::new(File::open(SLICE_PATH).unwrap());
+        let parser = SliceParser::new(file).unwrap();
+        parser.map(|frame| sum(&frame.unwrap().values)).sum()
+    });
+    bench("MmapSlice::frame", || {
+        let slice = MmapSlice::open(SLICE_PATH).unwrap();
+        (0..slice.n_frames())
+            .map(|n| sum(&slice.frame(n).unwrap().values))
+            .sum()
+    });
+    bench("MmapSlice::copy_values", || {
+        let slice = MmapSlice::open(SLICE_PATH).unwrap();
+        let mut values = Vec::new();
+        (0..slice.n_frames())
+            .map(|n| {
+                slice.copy_values(n, &mut values).unwrap();
+                sum(&values)
+            })
+            .sum()
+    });
+    bench("MmapSlice::value_bytes", || {
+        let slice = MmapSlice::open(SLICE_PATH).unwrap();
+        (0..slice.n_frames())
+            .map(|n| {
+                slice
+                    .value_bytes(n)
+                    .unwrap()
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+                    .sum::<f64>()
+            })
+            .sum()
+    });
+}
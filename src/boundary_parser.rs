@@ -0,0 +1,456 @@
+use crate::fortran_record::OffsetReader;
+use crate::{Dimensions, RecordError, RecordFormat};
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoundaryFile {
+    pub header: BoundaryHeader,
+    pub frames: Vec<BoundaryFrame>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoundaryHeader {
+    pub quantity: String,
+    pub short_name: String,
+    pub units: String,
+    pub patches: Vec<Patch>,
+}
+
+/// A rectangular patch of boundary faces on a single side of an obstruction
+/// or mesh boundary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Patch {
+    pub dimensions: Dimensions,
+    /// The orientation of the patch: ±1, ±2, ±3 for faces normal to the x, y
+    /// and z axes respectively.
+    pub ior: i32,
+    /// The 1-based index of the obstruction the patch belongs to within its
+    /// mesh, or 0 for patches on the mesh boundary.
+    pub obst_index: i32,
+    /// The 1-based index of the mesh. Older versions of FDS do not write this.
+    pub mesh: Option<i32>,
+}
+
+impl Patch {
+    pub fn n_values(&self) -> usize {
+        self.dimensions.n_values() as usize
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoundaryFrame {
+    pub time: f32,
+    /// The values for each patch, in the same order as
+    /// [`BoundaryHeader::patches`].
+    pub values: Vec<Vec<f32>>,
+}
+
+#[derive(Debug)]
+pub enum ParseBoundaryError {
+    IOError(std::io::Error),
+    /// A record does not have the expected length, or the length markers at
+    /// its start and end differ. `offset` is the position of the start of the
+    /// record in bytes.
+    RecLengthError {
+        offset: u64,
+        expected: u64,
+        found: u64,
+    },
+    /// The patch at this index in the header has negative dimensions or a
+    /// maximum less than its minimum.
+    InvalidPatch(usize),
+    /// The data ends part way through a frame. This is expected while FDS is
+    /// still writing the file.
+    IncompleteFrame,
+}
+
+impl std::fmt::Display for ParseBoundaryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IOError(err) => write!(f, "could not read boundary file: {}", err),
+            Self::RecLengthError {
+                offset,
+                expected,
+                found,
+            } => write!(
+                f,
+                "record at byte {} has length {} but {} was expected",
+                offset, found, expected
+            ),
+            Self::InvalidPatch(index) => {
+                write!(f, "boundary file patch {} has invalid dimensions", index)
+            }
+            Self::IncompleteFrame => write!(f, "boundary file ends part way through a frame"),
+        }
+    }
+}
+
+impl std::error::Error for ParseBoundaryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            Self::IOError(ref e) => Some(e),
+            Self::RecLengthError { .. } | Self::InvalidPatch(_) | Self::IncompleteFrame => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BoundaryParser<R> {
+    reader: BufReader<R>,
+    pub header: BoundaryHeader,
+    current_frame: usize,
+    header_length: u64,
+    format: RecordFormat,
+}
+
+impl<R: Read> BoundaryParser<R> {
+    pub fn parse_frame(&mut self) -> Result<BoundaryFrame, ParseBoundaryError> {
+        let offset = self.header_length + self.frame_length() * self.current_frame as u64;
+        let mut reader = OffsetReader {
+            inner: &mut self.reader,
+            offset,
+            format: self.format,
+        };
+        let frame = match parse_boundary_frame_at(&self.header.patches, &mut reader) {
+            Err(ParseBoundaryError::IOError(err))
+                if err.kind() == std::io::ErrorKind::UnexpectedEof =>
+            {
+                Err(ParseBoundaryError::IncompleteFrame)
+            }
+            frame => frame,
+        };
+        if frame.is_ok() {
+            self.current_frame += 1;
+        }
+        frame
+    }
+    pub fn header_length(&self) -> u64 {
+        self.header_length
+    }
+    /// The record format of the file, which is detected from the header.
+    pub fn format(&self) -> RecordFormat {
+        self.format
+    }
+    pub fn frame_length(&self) -> u64 {
+        let time_length = self.format.record_length(4);
+        let data_length: u64 = self
+            .header
+            .patches
+            .iter()
+            .map(|patch| self.format.record_length(patch.n_values() as u64 * 4))
+            .sum();
+        time_length + data_length
+    }
+}
+
+impl<R: Read + Seek> BoundaryParser<R> {
+    pub fn new(input: R) -> Result<Self, ParseBoundaryError> {
+        let mut reader = BufReader::new(input);
+        let offset = reader
+            .stream_position()
+            .map_err(ParseBoundaryError::IOError)?;
+        let format = RecordFormat::detect(&mut reader).map_err(ParseBoundaryError::IOError)?;
+        let header = parse_boundary_header(&mut OffsetReader {
+            inner: &mut reader,
+            offset,
+            format,
+        })?;
+        let header_length = reader
+            .stream_position()
+            .map_err(ParseBoundaryError::IOError)?;
+        Ok(BoundaryParser {
+            reader,
+            header,
+            current_frame: 0,
+            header_length,
+            format,
+        })
+    }
+    pub fn seek_next_frame(&mut self) -> std::io::Result<()> {
+        self.reader.seek_relative(self.frame_length() as i64)?;
+        self.current_frame += 1;
+        Ok(())
+    }
+    pub fn seek_frame(&mut self, frame: usize) -> std::io::Result<u64> {
+        let position = self.reader.seek(SeekFrom::Start(
+            self.header_length() + self.frame_length() * (frame as u64),
+        ))?;
+        self.current_frame = frame;
+        Ok(position)
+    }
+    pub fn get_frame(&mut self, frame: usize) -> Result<BoundaryFrame, ParseBoundaryError> {
+        self.seek_frame(frame)
+            .map_err(ParseBoundaryError::IOError)?;
+        self.parse_frame()
+    }
+}
+
+/// Iterates over the frames from the current position. Iteration ends at the
+/// end of the data or at an incomplete frame, in which case the position is
+/// returned to the start of that frame, as for [`crate::SliceParser`].
+impl<R: Read + Seek> Iterator for BoundaryParser<R> {
+    type Item = Result<BoundaryFrame, ParseBoundaryError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.reader.fill_buf() {
+            Ok([]) => return None,
+            Ok(_) => (),
+            Err(err) => return Some(Err(ParseBoundaryError::IOError(err))),
+        }
+        match self.parse_frame() {
+            Err(ParseBoundaryError::IncompleteFrame) => match self.seek_frame(self.current_frame) {
+                Ok(_) => None,
+                Err(err) => Some(Err(ParseBoundaryError::IOError(err))),
+            },
+            frame => Some(frame),
+        }
+    }
+}
+
+/// Parse a boundary file. An incomplete frame at the end of the file is
+/// ignored, but any other error in the frames is returned.
+pub fn parse_boundary_file<R: Read + Seek>(i: &mut R) -> Result<BoundaryFile, ParseBoundaryError> {
+    let parser = BoundaryParser::new(i)?;
+    let header = parser.header.clone();
+    let frames = parser.collect::<Result<Vec<_>, _>>()?;
+    Ok(BoundaryFile { header, frames })
+}
+
+/// Parse a frame. Offsets in errors are relative to the start of the frame.
+pub fn parse_boundary_frame<R: Read>(
+    format: RecordFormat,
+    patches: &[Patch],
+    i: R,
+) -> Result<BoundaryFrame, ParseBoundaryError> {
+    parse_boundary_frame_at(
+        patches,
+        &mut OffsetReader {
+            inner: i,
+            offset: 0,
+            format,
+        },
+    )
+}
+
+fn parse_boundary_frame_at<R: Read>(
+    patches: &[Patch],
+    i: &mut OffsetReader<R>,
+) -> Result<BoundaryFrame, ParseBoundaryError> {
+    let time_record = parse_record(i, Some(4))?;
+    let time = match i.format.decode_f32s(&time_record).as_deref() {
+        Ok([time]) => *time,
+        _ => unreachable!("time record length has been checked"),
+    };
+    let mut values = Vec::with_capacity(patches.len());
+    for patch in patches {
+        let record = parse_record(i, Some(patch.n_values() as u64 * 4))?;
+        // The length has been checked so the record holds whole values.
+        values.push(i.format.decode_f32s(&record).unwrap_or_default());
+    }
+    Ok(BoundaryFrame { time, values })
+}
+
+fn parse_boundary_header<R: Read>(
+    i: &mut OffsetReader<R>,
+) -> Result<BoundaryHeader, ParseBoundaryError> {
+    let quantity = parse_record(i, None)?;
+    let short_name = parse_record(i, None)?;
+    let units = parse_record(i, None)?;
+    let n_patches_record = parse_record(i, Some(4))?;
+    let n_patches = match i.format.decode_i32s(&n_patches_record).as_deref() {
+        Ok([n_patches]) => (*n_patches).max(0) as usize,
+        _ => unreachable!("patch count record length has been checked"),
+    };
+    let mut patches = Vec::with_capacity(n_patches);
+    for index in 0..n_patches {
+        let offset = i.offset;
+        let record = parse_record(i, None)?;
+        // FDS 6 writes the mesh number as a ninth value.
+        if record.len() != 32 && record.len() != 36 {
+            return Err(ParseBoundaryError::RecLengthError {
+                offset,
+                expected: 36,
+                found: record.len() as u64,
+            });
+        }
+        // The length has been checked so the record holds whole values.
+        let values = i.format.decode_i32s(&record).unwrap_or_default();
+        if values[..6].iter().any(|v| *v < 0) {
+            return Err(ParseBoundaryError::InvalidPatch(index));
+        }
+        let dimensions = Dimensions {
+            i_min: values[0] as u32,
+            i_max: values[1] as u32,
+            j_min: values[2] as u32,
+            j_max: values[3] as u32,
+            k_min: values[4] as u32,
+            k_max: values[5] as u32,
+        };
+        if !dimensions.is_valid() {
+            return Err(ParseBoundaryError::InvalidPatch(index));
+        }
+        patches.push(Patch {
+            dimensions,
+            ior: values[6],
+            obst_index: values[7],
+            mesh: values.get(8).copied(),
+        });
+    }
+    Ok(BoundaryHeader {
+        quantity: String::from_utf8_lossy(&quantity).into_owned(),
+        short_name: String::from_utf8_lossy(&short_name).into_owned(),
+        units: String::from_utf8_lossy(&units).into_owned(),
+        patches,
+    })
+}
+
+/// Parse the data from a record, ensuring the record length tags at the start
+/// and finish match, and that the length is as expected if given.
+fn parse_record<R: Read>(
+    i: &mut OffsetReader<R>,
+    expected: Option<u64>,
+) -> Result<Vec<u8>, ParseBoundaryError> {
+    let offset = i.offset;
+    let format = i.format;
+    let record = match expected {
+        Some(expected) => format.read_record_expecting(i, expected),
+        None => format.read_record(i),
+    };
+    record.map_err(|err| match err {
+        RecordError::IOError(err) => ParseBoundaryError::IOError(err),
+        RecordError::RecLengthError { expected, found } => ParseBoundaryError::RecLengthError {
+            offset,
+            expected,
+            found,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fortran_record::test_records::{formats, RecordWriter};
+
+    /// Build a boundary file with two patches (2x3 and 1x1) and `n_frames`
+    /// frames, where each value encodes the frame, patch and position.
+    fn make_boundary_file(format: RecordFormat, n_frames: usize) -> Vec<u8> {
+        let mut out = RecordWriter::new(format);
+        out.label("WALL TEMPERATURE").label("temp").label("C");
+        out.i32s(&[2]);
+        for patch in &[[0, 1, 4, 6, 3, 3, 3, 5, 1], [2, 2, 0, 0, 1, 1, -1, 0, 1]] {
+            out.i32s(patch);
+        }
+        for frame in 0..n_frames {
+            out.f32s(&[frame as f32 * 0.5]);
+            for (p, n) in [6, 1].iter().enumerate() {
+                let values: Vec<f32> = (0..*n).map(|v| (frame * 100 + p * 10 + v) as f32).collect();
+                out.f32s(&values);
+            }
+        }
+        out.data
+    }
+
+    #[test]
+    fn parse_boundary_simple() {
+        let data = make_boundary_file(RecordFormat::default(), 4);
+        let result = parse_boundary_file(&mut std::io::Cursor::new(&data)).unwrap();
+        assert_eq!(result.header.quantity.trim(), "WALL TEMPERATURE");
+        assert_eq!(result.header.short_name.trim(), "temp");
+        assert_eq!(result.header.units.trim(), "C");
+        assert_eq!(result.header.patches.len(), 2);
+        assert_eq!(
+            result.header.patches[0],
+            Patch {
+                dimensions: Dimensions {
+                    i_min: 0,
+                    i_max: 1,
+                    j_min: 4,
+                    j_max: 6,
+                    k_min: 3,
+                    k_max: 3,
+                },
+                ior: 3,
+                obst_index: 5,
+                mesh: Some(1),
+            }
+        );
+        assert_eq!(result.header.patches[1].ior, -1);
+        assert_eq!(result.frames.len(), 4);
+        assert_eq!(result.frames[3].time, 1.5);
+        assert_eq!(
+            result.frames[3].values,
+            vec![vec![300., 301., 302., 303., 304., 305.], vec![310.]]
+        );
+        let mut parser = BoundaryParser::new(std::io::Cursor::new(&data)).unwrap();
+        for i in (0..4).rev() {
+            assert_eq!(parser.get_frame(i).unwrap(), result.frames[i]);
+        }
+
+        let format = formats()[1];
+        let data = make_boundary_file(format, 4);
+        let mut parser = BoundaryParser::new(std::io::Cursor::new(&data)).unwrap();
+        assert_eq!(parser.format(), format);
+        assert_eq!(parser.header, result.header);
+        assert_eq!(parser.get_frame(3).unwrap(), result.frames[3]);
+    }
+
+    #[test]
+    fn parse_boundary_truncated() {
+        let mut data = make_boundary_file(RecordFormat::default(), 2);
+        data.truncate(data.len() - 2);
+        let mut input = std::io::Cursor::new(&data);
+        let result = parse_boundary_file(&mut input).unwrap();
+        assert_eq!(result.frames.len(), 1);
+
+        // The parser stops before the partial frame, so it can be read once
+        // the rest of the frame has been written.
+        let mut parser = BoundaryParser::new(std::io::Cursor::new(&data)).unwrap();
+        assert_eq!(parser.by_ref().count(), 1);
+        assert_eq!(
+            parser.reader.stream_position().unwrap(),
+            parser.header_length() + parser.frame_length()
+        );
+        assert!(matches!(
+            parser.parse_frame(),
+            Err(ParseBoundaryError::IncompleteFrame)
+        ));
+
+        // A record with the wrong length is still an error.
+        let mut data = make_boundary_file(RecordFormat::default(), 1);
+        let header_length = BoundaryParser::new(std::io::Cursor::new(&data))
+            .unwrap()
+            .header_length() as usize;
+        let time_at = header_length;
+        data[time_at..time_at + 4].copy_from_slice(&8u32.to_le_bytes());
+        let result = parse_boundary_file(&mut std::io::Cursor::new(&data));
+        assert!(matches!(
+            result,
+            Err(ParseBoundaryError::RecLengthError {
+                offset,
+                expected: 4,
+                found: 8,
+            }) if offset == header_length as u64
+        ));
+    }
+
+    #[test]
+    fn parse_boundary_invalid_patch() {
+        let format = RecordFormat::default();
+        let data = make_boundary_file(format, 1);
+        // The i_max of the second patch, after the labels, the number of
+        // patches, the first patch, the record marker and i_min.
+        let at = (3 * format.record_length(30) + format.record_length(4) + format.record_length(36))
+            as usize
+            + 8;
+        for i_max in [1, -1].iter() {
+            let mut data = data.clone();
+            data[at..at + 4].copy_from_slice(&format.encode_i32s(&[*i_max]));
+            let result = parse_boundary_file(&mut std::io::Cursor::new(&data));
+            assert!(matches!(result, Err(ParseBoundaryError::InvalidPatch(1))));
+        }
+    }
+}
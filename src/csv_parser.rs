@@ -156,6 +156,12 @@ impl CsvDataBlock {
         Some(dv)
     }
 
+    /// Return the units of the first vector with the given name.
+    pub fn units(&self, name: &str) -> Option<&str> {
+        let index = self.names.iter().position(|x| x == name)?;
+        self.units.get(index).map(|units| units.as_str())
+    }
+
     pub fn default_vecs(&self) -> Vec<DataVector<f64, SmvValue>> {
         let mut names = self.names.iter();
         // The first name is our default x name.
@@ -0,0 +1,521 @@
+use std::io::{BufRead, Read, Seek, SeekFrom, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+/// The layout of the records in a Fortran unformatted sequential file, as
+/// written by FDS. Each record is a length marker, the data, then the length
+/// marker again. The byte order applies to both the markers and the data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RecordFormat {
+    endianness: Endianness,
+    marker_width: MarkerWidth,
+}
+
+/// The width of the length markers of a record.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MarkerWidth {
+    Four,
+    Eight,
+}
+
+impl MarkerWidth {
+    /// The width in bytes.
+    pub fn bytes(self) -> usize {
+        match self {
+            Self::Four => 4,
+            Self::Eight => 8,
+        }
+    }
+}
+
+impl Default for RecordFormat {
+    /// Little-endian with 4-byte markers, which most compilers write.
+    fn default() -> Self {
+        Self {
+            endianness: Endianness::Little,
+            marker_width: MarkerWidth::Four,
+        }
+    }
+}
+
+impl RecordFormat {
+    pub fn new(endianness: Endianness, marker_width: MarkerWidth) -> Self {
+        Self {
+            endianness,
+            marker_width,
+        }
+    }
+
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    pub fn marker_width(&self) -> MarkerWidth {
+        self.marker_width
+    }
+
+    /// Detect the format from the first record of a file without consuming
+    /// it. The format is the one for which the markers at the start and end of
+    /// the first record agree. If there is none, the default is returned so
+    /// that reading the first record gives a useful error.
+    pub fn detect<R: BufRead>(input: &mut R) -> std::io::Result<Self> {
+        let bytes = input.fill_buf()?;
+        Ok(Self::detect_bytes(bytes).unwrap_or_default())
+    }
+
+    fn detect_bytes(bytes: &[u8]) -> Option<Self> {
+        let candidates = [
+            Self::new(Endianness::Little, MarkerWidth::Four),
+            Self::new(Endianness::Big, MarkerWidth::Four),
+            Self::new(Endianness::Little, MarkerWidth::Eight),
+            Self::new(Endianness::Big, MarkerWidth::Eight),
+        ];
+        candidates.iter().copied().find(|format| {
+            let width = format.marker_width.bytes();
+            let start = match bytes.get(..width) {
+                Some(marker) => format.decode_marker(marker),
+                None => return false,
+            };
+            let end_offset = match (start as usize).checked_add(width) {
+                Some(offset) => offset,
+                None => return false,
+            };
+            match end_offset
+                .checked_add(width)
+                .and_then(|end| bytes.get(end_offset..end))
+            {
+                Some(marker) => format.decode_marker(marker) == start,
+                None => false,
+            }
+        })
+    }
+
+    /// The length of a record holding `data_length` bytes, including its
+    /// markers.
+    pub fn record_length(&self, data_length: u64) -> u64 {
+        data_length + 2 * self.marker_width.bytes() as u64
+    }
+
+    pub fn read_marker<R: Read>(&self, i: &mut R) -> std::io::Result<u64> {
+        let mut buf = [0u8; 8];
+        let marker = &mut buf[..self.marker_width.bytes()];
+        i.read_exact(marker)?;
+        Ok(self.decode_marker(marker))
+    }
+
+    fn decode_marker(&self, bytes: &[u8]) -> u64 {
+        let mut buf = [0u8; 8];
+        match self.endianness {
+            Endianness::Little => {
+                buf[..bytes.len()].copy_from_slice(bytes);
+                u64::from_le_bytes(buf)
+            }
+            Endianness::Big => {
+                buf[8 - bytes.len()..].copy_from_slice(bytes);
+                u64::from_be_bytes(buf)
+            }
+        }
+    }
+
+    /// Read the data from a record, ensuring the markers at the start and end
+    /// match.
+    pub fn read_record<R: Read>(&self, i: &mut R) -> Result<Vec<u8>, RecordError> {
+        let rec_length = self.read_marker(i).map_err(RecordError::IOError)?;
+        self.read_record_data(i, rec_length)
+    }
+
+    /// Read the data from a record which must be `expected` bytes long. The
+    /// length is checked before the data is read.
+    pub fn read_record_expecting<R: Read>(
+        &self,
+        i: &mut R,
+        expected: u64,
+    ) -> Result<Vec<u8>, RecordError> {
+        let rec_length = self.read_marker(i).map_err(RecordError::IOError)?;
+        if rec_length != expected {
+            return Err(RecordError::RecLengthError {
+                expected,
+                found: rec_length,
+            });
+        }
+        self.read_record_data(i, rec_length)
+    }
+
+    fn read_record_data<R: Read>(
+        &self,
+        i: &mut R,
+        rec_length: u64,
+    ) -> Result<Vec<u8>, RecordError> {
+        // The data is read in pieces rather than allocated up front as the
+        // length may be corrupt.
+        let mut rec_bytes = Vec::new();
+        i.by_ref()
+            .take(rec_length)
+            .read_to_end(&mut rec_bytes)
+            .map_err(RecordError::IOError)?;
+        if (rec_bytes.len() as u64) < rec_length {
+            return Err(RecordError::IOError(
+                std::io::ErrorKind::UnexpectedEof.into(),
+            ));
+        }
+        let check_length = self.read_marker(i).map_err(RecordError::IOError)?;
+        if check_length != rec_length {
+            return Err(RecordError::RecLengthError {
+                expected: rec_length,
+                found: check_length,
+            });
+        }
+        Ok(rec_bytes)
+    }
+
+    /// Write the data as a record.
+    pub fn write_record<W: Write>(&self, w: &mut W, data: &[u8]) -> std::io::Result<()> {
+        let length = data.len() as u64;
+        let marker = match self.endianness {
+            Endianness::Little => length.to_le_bytes(),
+            Endianness::Big => length.to_be_bytes(),
+        };
+        let marker = match self.endianness {
+            Endianness::Little => &marker[..self.marker_width.bytes()],
+            Endianness::Big => &marker[8 - self.marker_width.bytes()..],
+        };
+        w.write_all(marker)?;
+        w.write_all(data)?;
+        w.write_all(marker)
+    }
+
+    /// Decode the values of a record. Returns an error if the record is not
+    /// a whole number of values.
+    pub fn decode_i32s(&self, bytes: &[u8]) -> Result<Vec<i32>, RecordError> {
+        self.decode(bytes, i32::from_le_bytes, i32::from_be_bytes)
+    }
+    pub fn decode_u32s(&self, bytes: &[u8]) -> Result<Vec<u32>, RecordError> {
+        self.decode(bytes, u32::from_le_bytes, u32::from_be_bytes)
+    }
+    pub fn decode_f32s(&self, bytes: &[u8]) -> Result<Vec<f32>, RecordError> {
+        self.decode(bytes, f32::from_le_bytes, f32::from_be_bytes)
+    }
+    pub fn decode_u16s(&self, bytes: &[u8]) -> Result<Vec<u16>, RecordError> {
+        let chunks = bytes.chunks_exact(2);
+        check_whole_values(bytes, chunks.remainder())?;
+        Ok(chunks
+            .map(|b| match self.endianness {
+                Endianness::Little => u16::from_le_bytes([b[0], b[1]]),
+                Endianness::Big => u16::from_be_bytes([b[0], b[1]]),
+            })
+            .collect())
+    }
+
+    pub fn encode_i32s(&self, values: &[i32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|v| match self.endianness {
+                Endianness::Little => v.to_le_bytes(),
+                Endianness::Big => v.to_be_bytes(),
+            })
+            .collect()
+    }
+    pub fn encode_f32s(&self, values: &[f32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|v| match self.endianness {
+                Endianness::Little => v.to_le_bytes(),
+                Endianness::Big => v.to_be_bytes(),
+            })
+            .collect()
+    }
+    pub fn encode_u16s(&self, values: &[u16]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|v| match self.endianness {
+                Endianness::Little => v.to_le_bytes(),
+                Endianness::Big => v.to_be_bytes(),
+            })
+            .collect()
+    }
+
+    fn decode<T>(
+        &self,
+        bytes: &[u8],
+        from_le: fn([u8; 4]) -> T,
+        from_be: fn([u8; 4]) -> T,
+    ) -> Result<Vec<T>, RecordError> {
+        let chunks = bytes.chunks_exact(4);
+        check_whole_values(bytes, chunks.remainder())?;
+        let from_bytes = match self.endianness {
+            Endianness::Little => from_le,
+            Endianness::Big => from_be,
+        };
+        Ok(chunks
+            .map(|b| from_bytes([b[0], b[1], b[2], b[3]]))
+            .collect())
+    }
+}
+
+/// The length expected of a record is the length of the whole values it
+/// holds.
+fn check_whole_values(bytes: &[u8], remainder: &[u8]) -> Result<(), RecordError> {
+    if remainder.is_empty() {
+        Ok(())
+    } else {
+        Err(RecordError::RecLengthError {
+            expected: (bytes.len() - remainder.len()) as u64,
+            found: bytes.len() as u64,
+        })
+    }
+}
+
+/// A reader of records which keeps track of its position in the file, so that
+/// errors can report where they occurred.
+pub(crate) struct OffsetReader<R> {
+    pub(crate) inner: R,
+    pub(crate) offset: u64,
+    pub(crate) format: RecordFormat,
+}
+
+impl<R> OffsetReader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            offset: 0,
+            format: RecordFormat::default(),
+        }
+    }
+}
+
+impl<R: Read> Read for OffsetReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+/// The offsets of the frames of a file in which frames have no fixed
+/// length, such as particle, isosurface and 3D smoke files. Offsets are
+/// recorded as they are found when seeking.
+#[derive(Clone, Debug)]
+pub(crate) struct FrameOffsets {
+    /// The known offsets of the start of each frame, beginning with the first
+    /// frame.
+    offsets: Vec<u64>,
+}
+
+impl FrameOffsets {
+    pub(crate) fn new(header_length: u64) -> Self {
+        Self {
+            offsets: vec![header_length],
+        }
+    }
+
+    pub(crate) fn header_length(&self) -> u64 {
+        self.offsets[0]
+    }
+
+    pub(crate) fn get(&self, frame: usize) -> Option<u64> {
+        self.offsets.get(frame).copied()
+    }
+
+    /// Record the offset of the frame after the last known frame, which is
+    /// `length` bytes long.
+    pub(crate) fn push_length(&mut self, length: u64) {
+        let offset = self.offsets[self.offsets.len() - 1] + length;
+        self.offsets.push(offset);
+    }
+
+    /// Seek to the start of a frame, returning its offset. Frames which have
+    /// not been seen before are found by skipping over the frames before them
+    /// with `skip_frame`, which must leave the reader at the start of the
+    /// next frame.
+    pub(crate) fn seek<R, E, F>(
+        &mut self,
+        reader: &mut R,
+        frame: usize,
+        mut skip_frame: F,
+    ) -> Result<u64, E>
+    where
+        R: Seek,
+        E: From<RecordError>,
+        F: FnMut(&mut R) -> Result<(), E>,
+    {
+        let mut current = frame.min(self.offsets.len() - 1);
+        reader
+            .seek(SeekFrom::Start(self.offsets[current]))
+            .map_err(RecordError::IOError)?;
+        while current < frame {
+            skip_frame(reader)?;
+            current += 1;
+            if current == self.offsets.len() {
+                let offset = reader.stream_position().map_err(RecordError::IOError)?;
+                self.offsets.push(offset);
+            }
+        }
+        Ok(self.offsets[frame])
+    }
+}
+
+/// Parse the frame at the current position with `parse_frame`, or return
+/// None if there is no more data, for use in the `Iterator` implementations
+/// of parsers.
+pub(crate) fn next_frame<R, T, E, F>(reader: &mut R, parse_frame: F) -> Option<Result<T, E>>
+where
+    R: BufRead,
+    E: From<RecordError>,
+    F: FnOnce(&mut R) -> Result<T, E>,
+{
+    // Stop cleanly if there is no more data at the start of a frame.
+    match reader.fill_buf() {
+        Ok([]) => return None,
+        Ok(_) => (),
+        Err(err) => return Some(Err(RecordError::IOError(err).into())),
+    }
+    Some(parse_frame(reader))
+}
+
+#[derive(Debug)]
+pub enum RecordError {
+    IOError(std::io::Error),
+    /// A record does not have the expected length, the markers at its start
+    /// and end differ, or it does not hold a whole number of values.
+    RecLengthError {
+        expected: u64,
+        found: u64,
+    },
+}
+
+impl std::fmt::Display for RecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IOError(err) => write!(f, "could not read record: {}", err),
+            Self::RecLengthError { expected, found } => write!(
+                f,
+                "record has length {} but {} was expected",
+                found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            Self::IOError(ref e) => Some(e),
+            Self::RecLengthError { .. } => None,
+        }
+    }
+}
+
+/// Builds the files used by the tests of the readers. These files are
+/// written by hand in the layouts FDS uses, rather than taken from FDS runs,
+/// so the tests only check that the readers agree with those layouts.
+#[cfg(test)]
+pub(crate) mod test_records {
+    use super::*;
+
+    /// The default record format, and big-endian with 8-byte record markers.
+    pub(crate) fn formats() -> [RecordFormat; 2] {
+        [
+            RecordFormat::default(),
+            RecordFormat::new(Endianness::Big, MarkerWidth::Eight),
+        ]
+    }
+
+    /// Writes records in a given format to a buffer.
+    pub(crate) struct RecordWriter {
+        pub(crate) format: RecordFormat,
+        pub(crate) data: Vec<u8>,
+    }
+
+    impl RecordWriter {
+        pub(crate) fn new(format: RecordFormat) -> Self {
+            Self {
+                format,
+                data: Vec::new(),
+            }
+        }
+        pub(crate) fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+            self.format.write_record(&mut self.data, bytes).unwrap();
+            self
+        }
+        pub(crate) fn i32s(&mut self, values: &[i32]) -> &mut Self {
+            let bytes = self.format.encode_i32s(values);
+            self.bytes(&bytes)
+        }
+        pub(crate) fn f32s(&mut self, values: &[f32]) -> &mut Self {
+            let bytes = self.format.encode_f32s(values);
+            self.bytes(&bytes)
+        }
+        /// A label padded with spaces to the 30 characters FDS writes.
+        pub(crate) fn label(&mut self, label: &str) -> &mut Self {
+            self.bytes(format!("{:<30}", label).as_bytes())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_and_read_records() {
+        for endianness in [Endianness::Little, Endianness::Big].iter() {
+            for marker_width in [MarkerWidth::Four, MarkerWidth::Eight].iter() {
+                let format = RecordFormat::new(*endianness, *marker_width);
+                let mut data = Vec::new();
+                format
+                    .write_record(&mut data, b"TEMPERATURE                   ")
+                    .unwrap();
+                format
+                    .write_record(&mut data, &format.encode_i32s(&[1, -2, 3]))
+                    .unwrap();
+                let mut input = std::io::Cursor::new(&data);
+                assert_eq!(RecordFormat::detect(&mut input).unwrap(), format);
+                assert_eq!(input.position(), 0);
+                assert_eq!(format.read_record(&mut input).unwrap().len(), 30);
+                let record = format.read_record_expecting(&mut input, 12).unwrap();
+                assert_eq!(format.decode_i32s(&record).unwrap(), vec![1, -2, 3]);
+                assert_eq!(
+                    format.record_length(30) + format.record_length(12),
+                    data.len() as u64
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn record_errors() {
+        let format = RecordFormat::default();
+        let mut data = Vec::new();
+        format.write_record(&mut data, &[0; 8]).unwrap();
+        assert!(matches!(
+            format.read_record_expecting(&mut std::io::Cursor::new(&data), 4),
+            Err(RecordError::RecLengthError {
+                expected: 4,
+                found: 8
+            })
+        ));
+        data[12] = 9;
+        assert!(matches!(
+            format.read_record(&mut std::io::Cursor::new(&data)),
+            Err(RecordError::RecLengthError {
+                expected: 8,
+                found: 9
+            })
+        ));
+        assert!(matches!(
+            format.read_record(&mut std::io::Cursor::new(&data[..10])),
+            Err(RecordError::IOError(_))
+        ));
+        assert!(matches!(
+            format.decode_f32s(&[0; 6]),
+            Err(RecordError::RecLengthError {
+                expected: 4,
+                found: 6
+            })
+        ));
+    }
+}
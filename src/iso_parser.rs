@@ -0,0 +1,372 @@
+use crate::fortran_record::{next_frame, FrameOffsets};
+use crate::{RecordError, RecordFormat, Xyz};
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Seek;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IsoFile {
+    pub header: IsoHeader,
+    pub frames: Vec<IsoFrame>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IsoHeader {
+    pub version: i32,
+    /// The values of the quantity at which isosurfaces are drawn. The
+    /// quantity itself is given by the ISOG or ISOF entry of the smv file.
+    pub levels: Vec<f32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IsoFrame {
+    pub time: f32,
+    /// The positions of the vertices of every surface in the frame.
+    pub vertices: Vec<[f32; 3]>,
+    /// The isosurface for each level, in the same order as
+    /// [`IsoHeader::levels`].
+    pub surfaces: Vec<IsoSurface>,
+}
+
+impl IsoFrame {
+    pub fn positions(&self) -> Vec<Xyz> {
+        self.vertices
+            .iter()
+            .map(|[x, y, z]| Xyz::new(*x as f64, *y as f64, *z as f64))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IsoSurface {
+    /// The indices into [`IsoFrame::vertices`] of the vertices of each
+    /// triangle, counting from 0.
+    pub triangles: Vec<[u32; 3]>,
+}
+
+#[derive(Debug)]
+pub enum ParseIsoError {
+    Record(RecordError),
+    /// The first record is not the integer 1, so this is not an isosurface
+    /// file.
+    InvalidHeader,
+    /// A triangle refers to a vertex which does not exist.
+    InvalidTriangle,
+    /// A triangle belongs to a level which does not exist.
+    InvalidLevel(i32),
+    /// A count of levels, vertices or triangles is negative.
+    InvalidCount(i32),
+}
+
+impl std::fmt::Display for ParseIsoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Record(err) => write!(f, "could not read isosurface file: {}", err),
+            Self::InvalidHeader => write!(f, "isosurface file has an invalid header"),
+            Self::InvalidTriangle => write!(f, "isosurface triangle has an invalid vertex index"),
+            Self::InvalidLevel(level) => {
+                write!(f, "isosurface triangle has an invalid level {}", level)
+            }
+            Self::InvalidCount(n) => write!(f, "isosurface file has a count of {}", n),
+        }
+    }
+}
+
+impl std::error::Error for ParseIsoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            Self::Record(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<RecordError> for ParseIsoError {
+    fn from(err: RecordError) -> Self {
+        Self::Record(err)
+    }
+}
+
+/// A reader for isosurface (.iso) files. As the size of the surfaces changes
+/// between frames, frames have no fixed length. The offsets of frames are
+/// therefore recorded as they are found when seeking.
+#[derive(Debug)]
+pub struct IsoParser<R> {
+    reader: BufReader<R>,
+    pub header: IsoHeader,
+    frame_offsets: FrameOffsets,
+    format: RecordFormat,
+}
+
+impl<R: Read> IsoParser<R> {
+    pub fn parse_frame(&mut self) -> Result<IsoFrame, ParseIsoError> {
+        parse_iso_frame(self.format, self.header.levels.len(), &mut self.reader)
+    }
+    pub fn header_length(&self) -> u64 {
+        self.frame_offsets.header_length()
+    }
+    /// The record format of the file, which is detected from the header.
+    pub fn format(&self) -> RecordFormat {
+        self.format
+    }
+}
+
+impl<R: Read + Seek> IsoParser<R> {
+    pub fn new(input: R) -> Result<Self, ParseIsoError> {
+        let mut reader = BufReader::new(input);
+        let format = RecordFormat::detect(&mut reader).map_err(RecordError::IOError)?;
+        let header = parse_iso_header(format, &mut reader)?;
+        let header_length = reader.stream_position().map_err(RecordError::IOError)?;
+        Ok(IsoParser {
+            reader,
+            header,
+            frame_offsets: FrameOffsets::new(header_length),
+            format,
+        })
+    }
+    /// Seek to the start of a frame, returning its offset.
+    pub fn seek_frame(&mut self, frame: usize) -> Result<u64, ParseIsoError> {
+        let format = self.format;
+        self.frame_offsets.seek(&mut self.reader, frame, |reader| {
+            skip_iso_frame(format, reader)
+        })
+    }
+    pub fn get_frame(&mut self, frame: usize) -> Result<IsoFrame, ParseIsoError> {
+        self.seek_frame(frame)?;
+        self.parse_frame()
+    }
+}
+
+impl<R: Read> Iterator for IsoParser<R> {
+    type Item = Result<IsoFrame, ParseIsoError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (format, n_levels) = (self.format, self.header.levels.len());
+        next_frame(&mut self.reader, |reader| {
+            parse_iso_frame(format, n_levels, reader)
+        })
+    }
+}
+
+pub fn parse_iso_file<R: Read + Seek>(i: &mut R) -> Result<IsoFile, ParseIsoError> {
+    let parser = IsoParser::new(i)?;
+    let header = parser.header.clone();
+    let frames = parser.collect::<Result<Vec<_>, _>>()?;
+    Ok(IsoFile { header, frames })
+}
+
+/// Parse a frame. This holds the time and the number of vertices and
+/// triangles. If there are any vertices, these are followed by the vertex
+/// coordinates, the vertex indices of each triangle counting from 1, and the
+/// level of each triangle counting from 1.
+pub fn parse_iso_frame<R: Read>(
+    format: RecordFormat,
+    n_levels: usize,
+    mut i: R,
+) -> Result<IsoFrame, ParseIsoError> {
+    // The time is followed by an unused integer.
+    let time = format.decode_f32s(&format.read_record_expecting(&mut i, 8)?[..4])?[0];
+    let (n_vertices, n_triangles) = parse_frame_counts(format, &mut i)?;
+    let mut surfaces = vec![IsoSurface::default(); n_levels];
+    if n_vertices == 0 {
+        return Ok(IsoFrame {
+            time,
+            vertices: vec![],
+            surfaces,
+        });
+    }
+    let record = format.read_record_expecting(&mut i, n_vertices as u64 * 3 * 4)?;
+    let vertices = format
+        .decode_f32s(&record)?
+        .chunks_exact(3)
+        .map(|v| [v[0], v[1], v[2]])
+        .collect();
+    let record = format.read_record_expecting(&mut i, n_triangles as u64 * 3 * 4)?;
+    let indices = format.decode_i32s(&record)?;
+    if indices
+        .iter()
+        .any(|index| *index < 1 || *index as usize > n_vertices)
+    {
+        return Err(ParseIsoError::InvalidTriangle);
+    }
+    let record = format.read_record_expecting(&mut i, n_triangles as u64 * 4)?;
+    for (level, t) in format
+        .decode_i32s(&record)?
+        .into_iter()
+        .zip(indices.chunks_exact(3))
+    {
+        let surface = match level {
+            1..=i32::MAX => surfaces.get_mut(level as usize - 1),
+            _ => None,
+        }
+        .ok_or(ParseIsoError::InvalidLevel(level))?;
+        let triangle = [t[0] as u32 - 1, t[1] as u32 - 1, t[2] as u32 - 1];
+        surface.triangles.push(triangle);
+    }
+    Ok(IsoFrame {
+        time,
+        vertices,
+        surfaces,
+    })
+}
+
+/// Skip over a frame without reading the surfaces.
+fn skip_iso_frame<R: Read + Seek>(
+    format: RecordFormat,
+    i: &mut BufReader<R>,
+) -> Result<(), ParseIsoError> {
+    i.seek_relative(format.record_length(8) as i64)
+        .map_err(RecordError::IOError)?;
+    let (n_vertices, n_triangles) = parse_frame_counts(format, i)?;
+    if n_vertices > 0 {
+        let length = format.record_length(n_vertices as u64 * 3 * 4)
+            + format.record_length(n_triangles as u64 * 3 * 4)
+            + format.record_length(n_triangles as u64 * 4);
+        i.seek_relative(length as i64)
+            .map_err(RecordError::IOError)?;
+    }
+    Ok(())
+}
+
+fn parse_frame_counts<R: Read>(
+    format: RecordFormat,
+    i: &mut R,
+) -> Result<(usize, usize), ParseIsoError> {
+    match format
+        .decode_i32s(&format.read_record_expecting(i, 8)?)?
+        .as_slice()
+    {
+        [n_vertices, n_triangles] if *n_vertices >= 0 && *n_triangles >= 0 => {
+            Ok((*n_vertices as usize, *n_triangles as usize))
+        }
+        [n_vertices, n_triangles] => {
+            Err(ParseIsoError::InvalidCount((*n_vertices).min(*n_triangles)))
+        }
+        _ => unreachable!("count record length has been checked"),
+    }
+}
+
+fn parse_count<R: Read>(format: RecordFormat, i: &mut R) -> Result<usize, ParseIsoError> {
+    match format.decode_i32s(&format.read_record_expecting(i, 4)?)?[0] {
+        n if n >= 0 => Ok(n as usize),
+        n => Err(ParseIsoError::InvalidCount(n)),
+    }
+}
+
+/// Parse the header, which is written by FDS as the integer 1, the version,
+/// the number of levels and the levels. This is followed by a count of
+/// integer header values, which FDS writes as 0, and a record of two unused
+/// integers.
+fn parse_iso_header<R: Read>(format: RecordFormat, i: &mut R) -> Result<IsoHeader, ParseIsoError> {
+    if format.decode_i32s(&format.read_record(i)?)? != [1] {
+        return Err(ParseIsoError::InvalidHeader);
+    }
+    let version = format.decode_i32s(&format.read_record_expecting(i, 4)?)?[0];
+    let n_levels = parse_count(format, i)?;
+    let levels = format.decode_f32s(&format.read_record_expecting(i, n_levels as u64 * 4)?)?;
+    let n_header_values = parse_count(format, i)?;
+    if n_header_values > 0 {
+        format.read_record_expecting(i, n_header_values as u64 * 4)?;
+    }
+    format.read_record_expecting(i, 8)?;
+    Ok(IsoHeader { version, levels })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fortran_record::test_records::{formats, RecordWriter};
+
+    /// Build an isosurface file in the layout written by FDS 6, with two
+    /// levels. Every frame has a triangle of the first level, the middle
+    /// frames have no vertices and the last frame also has two triangles of
+    /// the second level.
+    fn make_iso_file(format: RecordFormat, n_frames: usize) -> Vec<u8> {
+        let mut out = RecordWriter::new(format);
+        out.i32s(&[1]).i32s(&[1]).i32s(&[2]).f32s(&[100., 200.]);
+        out.i32s(&[0]).i32s(&[0, 0]);
+        for frame in 0..n_frames {
+            let mut time = format.encode_f32s(&[frame as f32]);
+            time.extend(format.encode_i32s(&[0]));
+            out.bytes(&time);
+            if frame == 0 {
+                out.i32s(&[3, 1]);
+                out.f32s(&[1., 0., 0., 2., 0., 0., 1., 4., 1.]);
+                out.i32s(&[1, 2, 3]).i32s(&[1]);
+            } else if frame + 1 < n_frames {
+                out.i32s(&[0, 0]);
+            } else {
+                out.i32s(&[4, 3]);
+                out.f32s(&[0.; 12]);
+                out.i32s(&[1, 2, 3, 2, 3, 4, 4, 3, 1]).i32s(&[2, 1, 2]);
+            }
+        }
+        out.data
+    }
+
+    #[test]
+    fn parse_iso_simple() {
+        let data = make_iso_file(RecordFormat::default(), 3);
+        let result = parse_iso_file(&mut std::io::Cursor::new(&data)).unwrap();
+        assert_eq!(result.header.version, 1);
+        assert_eq!(result.header.levels, vec![100., 200.]);
+        assert_eq!(result.frames.len(), 3);
+        let frame = &result.frames[0];
+        assert_eq!(frame.surfaces[0].triangles, vec![[0, 1, 2]]);
+        assert!(frame.surfaces[1].triangles.is_empty());
+        assert_eq!(
+            frame.positions(),
+            vec![
+                Xyz::new(1., 0., 0.),
+                Xyz::new(2., 0., 0.),
+                Xyz::new(1., 4., 1.)
+            ]
+        );
+        assert!(result.frames[1].vertices.is_empty());
+        assert_eq!(result.frames[1].surfaces.len(), 2);
+        assert_eq!(result.frames[2].surfaces[0].triangles, vec![[1, 2, 3]]);
+        assert_eq!(
+            result.frames[2].surfaces[1].triangles,
+            vec![[0, 1, 2], [3, 2, 0]]
+        );
+
+        let mut parser = IsoParser::new(std::io::Cursor::new(&data)).unwrap();
+        for i in [2, 0, 1].iter() {
+            assert_eq!(parser.get_frame(*i).unwrap(), result.frames[*i]);
+        }
+
+        let format = formats()[1];
+        let data = make_iso_file(format, 3);
+        let mut parser = IsoParser::new(std::io::Cursor::new(&data)).unwrap();
+        assert_eq!(parser.format(), format);
+        assert_eq!(parser.header, result.header);
+        assert_eq!(parser.get_frame(2).unwrap(), result.frames[2]);
+    }
+
+    #[test]
+    fn parse_iso_invalid_triangle() {
+        let format = RecordFormat::default();
+        let data = make_iso_file(format, 1);
+        // The first vertex index of the triangle, after the header, the time,
+        // the counts, the vertices and the record marker.
+        let header_length = IsoParser::new(std::io::Cursor::new(&data))
+            .unwrap()
+            .header_length();
+        let at = (header_length
+            + format.record_length(8)
+            + format.record_length(8)
+            + format.record_length(36)) as usize
+            + 4;
+        let mut invalid = data.clone();
+        invalid[at..at + 4].copy_from_slice(&format.encode_i32s(&[0]));
+        let result = parse_iso_file(&mut std::io::Cursor::new(&invalid));
+        assert!(matches!(result, Err(ParseIsoError::InvalidTriangle)));
+
+        // The level of the triangle, after the vertex indices.
+        let at = at + format.record_length(12) as usize;
+        let mut invalid = data;
+        invalid[at..at + 4].copy_from_slice(&format.encode_i32s(&[3]));
+        let result = parse_iso_file(&mut std::io::Cursor::new(&invalid));
+        assert!(matches!(result, Err(ParseIsoError::InvalidLevel(3))));
+    }
+}
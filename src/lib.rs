@@ -1,11 +1,40 @@
 #![allow(dead_code)]
 pub mod smv_parser;
 pub use smv_parser::*;
+pub mod smv_writer;
 pub mod out_parser;
 pub use out_parser::*;
 pub mod csv_parser;
 pub use csv_parser::*;
 pub mod outputs;
 pub use outputs::*;
+pub mod fortran_record;
+pub use fortran_record::*;
 pub mod slice_parser;
 pub use slice_parser::*;
+pub mod boundary_parser;
+pub use boundary_parser::*;
+pub mod particle_parser;
+pub use particle_parser::*;
+pub mod smoke3d_parser;
+pub use smoke3d_parser::*;
+pub mod iso_parser;
+pub use iso_parser::*;
+pub mod plot3d_parser;
+pub use plot3d_parser::*;
+pub mod probe;
+pub use probe::*;
+pub mod slice_stats;
+pub use slice_stats::*;
+pub mod vtk_writer;
+pub use vtk_writer::*;
+pub mod slice_mmap;
+pub use slice_mmap::*;
+pub mod slice_writer;
+pub use slice_writer::*;
+pub mod slice_diff;
+pub use slice_diff::*;
+pub mod slice_stitch;
+pub use slice_stitch::*;
+pub mod slice_render;
+pub use slice_render::*;
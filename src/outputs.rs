@@ -1,23 +1,129 @@
-use crate::{CsvDataBlock, SmvFile, SmvValue};
+use crate::{
+    check_slice_grids, diff_slices, BoundaryHeader, BoundaryParser, CsvDataBlock, Slcf, SliceDiff,
+    SliceDiffError, SliceHeader, SliceParser, SlicePlane, SliceProbe, SmvFile, SmvValue,
+    StitchedGrid, StitchedSlice, Xb, Xyz,
+};
 use data_vector::DataVector;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// The time series of a device, along with where it is in the model.
+pub struct DeviceVector {
+    pub name: String,
+    pub quantity: String,
+    pub location: Xyz,
+    pub orientation: Xyz,
+    pub values: DataVector<f64, f64>,
+}
+
+/// A slice output, with the quantity it records and where it is in the
+/// model.
+#[derive(Clone, Debug)]
+pub struct SliceInfo<'a> {
+    pub slcf: &'a Slcf,
+    pub quantity: &'a str,
+    pub units: &'a str,
+    /// The 1-based index of the mesh the slice is in.
+    pub mesh: usize,
+    /// The physical bounds of the slice, or None if its mesh is not in the
+    /// SMV file.
+    pub bounds: Option<Xb>,
+}
 
 pub struct Outputs {
     pub smv_path: PathBuf,
     pub smv: SmvFile,
+    cache: OutputsCache,
+}
+
+/// Output files which have already been parsed, keyed by path. Each entry
+/// holds the modification time and length of the file when it was parsed, and
+/// is parsed again if either has changed since.
+#[derive(Default)]
+struct OutputsCache {
+    csv_blocks: HashMap<PathBuf, Cached<Arc<CsvDataBlock>>>,
+    slice_headers: HashMap<PathBuf, Cached<SliceHeader>>,
+    boundary_headers: HashMap<PathBuf, Cached<BoundaryHeader>>,
+}
+
+struct Cached<T> {
+    modified: SystemTime,
+    len: u64,
+    value: T,
+}
+
+impl OutputsCache {
+    fn csv_block(&mut self, path: &Path) -> Result<Arc<CsvDataBlock>, Box<dyn std::error::Error>> {
+        get_or_load(&mut self.csv_blocks, path, |path| {
+            Ok(Arc::new(CsvDataBlock::from_file(path)?))
+        })
+    }
+
+    fn slice_header(&mut self, path: &Path) -> Result<SliceHeader, Box<dyn std::error::Error>> {
+        get_or_load(&mut self.slice_headers, path, |path| {
+            Ok(SliceParser::new(std::fs::File::open(path)?)?.header)
+        })
+    }
+
+    fn boundary_header(
+        &mut self,
+        path: &Path,
+    ) -> Result<BoundaryHeader, Box<dyn std::error::Error>> {
+        get_or_load(&mut self.boundary_headers, path, |path| {
+            Ok(BoundaryParser::new(std::fs::File::open(path)?)?.header)
+        })
+    }
+}
+
+/// Return the cached value for a file if its modification time and length
+/// are the same as when it was loaded, otherwise load it again. FDS appends to
+/// output files while it runs, which changes the length even if the
+/// modification time has too coarse a resolution to change.
+fn get_or_load<T: Clone>(
+    cache: &mut HashMap<PathBuf, Cached<T>>,
+    path: &Path,
+    load: impl FnOnce(&Path) -> Result<T, Box<dyn std::error::Error>>,
+) -> Result<T, Box<dyn std::error::Error>> {
+    let metadata = std::fs::metadata(path)?;
+    let (modified, len) = (metadata.modified()?, metadata.len());
+    if let Some(cached) = cache.get(path) {
+        if cached.modified == modified && cached.len == len {
+            return Ok(cached.value.clone());
+        }
+    }
+    let value = load(path)?;
+    cache.insert(
+        path.to_path_buf(),
+        Cached {
+            modified,
+            len,
+            value: value.clone(),
+        },
+    );
+    Ok(value)
 }
 
 impl Outputs {
     pub fn new(smv_path: PathBuf) -> Self {
         let smv = SmvFile::from_file(&smv_path).expect("Could not read smv file");
-        Self { smv_path, smv }
+        Self {
+            smv_path,
+            smv,
+            cache: OutputsCache::default(),
+        }
     }
 
     pub fn from_file<P: AsRef<Path>>(smv_path: P) -> Result<Self, Box<dyn std::error::Error>> {
         let smv_path = PathBuf::from(smv_path.as_ref());
         let smv = SmvFile::from_file(&smv_path)
             .map_err(|err| format!("Could not read smv file: {err}"))?;
-        Ok(Self { smv_path, smv })
+        Ok(Self {
+            smv_path,
+            smv,
+            cache: OutputsCache::default(),
+        })
     }
 
     pub fn get_csv_vec(
@@ -25,7 +131,6 @@ impl Outputs {
         csv_type: String,
         vec_name: String,
     ) -> Result<Option<DataVector<f64, SmvValue>>, Box<dyn std::error::Error>> {
-        // TODO: add caching
         let csvf = if let Some(f) = self
             .smv
             .csvfs
@@ -36,15 +141,192 @@ impl Outputs {
         } else {
             return Ok(None);
         };
-        let smv_dir = PathBuf::from(self.smv_path.parent().unwrap());
-        let mut csv_file_path = PathBuf::new();
-        csv_file_path.push(smv_dir);
-        csv_file_path.push(csvf.filename.clone());
-        let data_block = CsvDataBlock::from_file(&csv_file_path)?;
+        let csv_file_path = self.output_path(&csvf.filename);
+        let data_block = self.cache.csv_block(&csv_file_path)?;
         let vec = data_block.make_data_vector("Time", &vec_name);
         Ok(vec)
     }
 
+    /// Get the time series of the device with the given name. FDS splits
+    /// devices across several numbered devc files when there are too many
+    /// columns, so each of these is searched. Returns None if the device does
+    /// not exist or was not found in any devc file.
+    pub fn device(
+        &mut self,
+        name: &str,
+    ) -> Result<Option<DeviceVector>, Box<dyn std::error::Error>> {
+        let device = match self.smv.device(name) {
+            Some(device) => device,
+            None => return Ok(None),
+        };
+        let devc_files = self.smv.csvfs.iter().filter(|csvf| csvf.type_ == "devc");
+        for csvf in devc_files {
+            let data_block = self.cache.csv_block(&self.output_path(&csvf.filename))?;
+            let vec = match data_block.make_data_vector("Time", device.name()) {
+                Some(vec) => vec,
+                None => continue,
+            };
+            let mut values = take_f64_vec(vec)?;
+            values.name = device.name().to_string();
+            values.x_name = "Time".to_string();
+            values.x_units = data_block.units("Time").unwrap_or_default().to_string();
+            values.y_name = device.quantity().to_string();
+            values.y_units = data_block
+                .units(device.name())
+                .unwrap_or_default()
+                .to_string();
+            return Ok(Some(DeviceVector {
+                name: device.name().to_string(),
+                quantity: device.quantity().to_string(),
+                location: device.xyz(),
+                orientation: device.orientation(),
+                values,
+            }));
+        }
+        Ok(None)
+    }
+
+    /// All the slices in the SMV file.
+    pub fn slices(&self) -> impl Iterator<Item = SliceInfo<'_>> {
+        self.smv.slcfs.iter().map(move |slcf| SliceInfo {
+            slcf,
+            quantity: &slcf.long_name,
+            units: &slcf.units,
+            mesh: slcf.mesh,
+            bounds: self.smv.slice_xb(slcf),
+        })
+    }
+
+    /// Open the file of a slice for reading.
+    pub fn open_slice(
+        &self,
+        slcf: &Slcf,
+    ) -> Result<SliceParser<std::fs::File>, Box<dyn std::error::Error>> {
+        let file = std::fs::File::open(self.output_path(&slcf.filename))?;
+        Ok(SliceParser::new(file)?)
+    }
+
+    /// Get the header of a slice file, given its path relative to the SMV
+    /// file.
+    pub fn slice_header(
+        &mut self,
+        filename: &str,
+    ) -> Result<SliceHeader, Box<dyn std::error::Error>> {
+        let path = self.output_path(filename);
+        self.cache.slice_header(&path)
+    }
+
+    /// Get the header of a boundary file, given its path relative to the SMV
+    /// file.
+    pub fn boundary_header(
+        &mut self,
+        filename: &str,
+    ) -> Result<BoundaryHeader, Box<dyn std::error::Error>> {
+        let path = self.output_path(filename);
+        self.cache.boundary_header(&path)
+    }
+
+    /// Get the time series of a quantity at a point, interpolated from a
+    /// slice which contains the point. If several slices contain the point
+    /// the first is used. Returns None if no slice of the quantity (e.g.
+    /// "TEMPERATURE") contains the point.
+    pub fn probe(
+        &mut self,
+        xyz: Xyz,
+        quantity: &str,
+    ) -> Result<Option<DataVector<f64, f64>>, Box<dyn std::error::Error>> {
+        for slcf in self
+            .smv
+            .slcfs
+            .iter()
+            .filter(|slcf| slcf.long_name == quantity)
+        {
+            let mesh = match self.smv.slice_mesh(slcf) {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+            let probe = match SliceProbe::new(mesh, slcf, xyz) {
+                Some(probe) => probe,
+                None => continue,
+            };
+            let parser = self.open_slice(slcf)?;
+            let mut values = new_f64_vec(
+                format!("{} at ({}, {}, {})", quantity, xyz.x, xyz.y, xyz.z),
+                "Time",
+                "s",
+                quantity,
+                &slcf.units,
+            );
+            for frame in parser {
+                let frame = frame?;
+                let y = probe
+                    .value(&frame)
+                    .ok_or("slice frame is smaller than its header")?;
+                values.insert(data_vector::Point {
+                    x: frame.time as f64,
+                    y,
+                });
+            }
+            return Ok(Some(values));
+        }
+        Ok(None)
+    }
+
+    /// Subtract a slice of another run from a slice of this one, e.g. the same
+    /// slice in two runs of a sensitivity study. The slices must have the
+    /// same quantity, units and dimensions and lie on the same grid. See
+    /// [`diff_slices`].
+    pub fn diff_slice(
+        &self,
+        slcf: &Slcf,
+        other: &Outputs,
+        other_slcf: &Slcf,
+    ) -> Result<SliceDiff, Box<dyn std::error::Error>> {
+        if slcf.cell_centred != other_slcf.cell_centred {
+            return Err(SliceDiffError::CellCentred.into());
+        }
+        if slcf.ijk != other_slcf.ijk {
+            return Err("slices cover different regions of their meshes".into());
+        }
+        let mesh = self.smv.slice_mesh(slcf).ok_or("slice mesh not found")?;
+        let other_mesh = other
+            .smv
+            .slice_mesh(other_slcf)
+            .ok_or("slice mesh not found")?;
+        check_slice_grids(mesh, other_mesh, &slcf.ijk, slcf.cell_centred)?;
+        Ok(diff_slices(
+            self.open_slice(slcf)?,
+            other.open_slice(other_slcf)?,
+        )?)
+    }
+
+    /// Read a slice plane from every mesh it crosses and stitch it into a
+    /// single field. See [`crate::SmvFile::slice_planes`].
+    pub fn stitch_slice(
+        &self,
+        plane: &SlicePlane,
+    ) -> Result<StitchedSlice, Box<dyn std::error::Error>> {
+        let grid = StitchedGrid::new(&self.smv, plane)
+            .ok_or("slice does not lie on the grid of its mesh")?;
+        let parsers = plane
+            .slcfs
+            .iter()
+            .map(|slcf| self.open_slice(slcf))
+            .collect::<Result<Vec<_>, _>>()?;
+        let frames = grid.read_frames(parsers)?;
+        Ok(StitchedSlice { grid, frames })
+    }
+
+    /// The path of an output file, which are given relative to the SMV file.
+    fn output_path(&self, filename: &str) -> PathBuf {
+        let mut path = PathBuf::new();
+        if let Some(smv_dir) = self.smv_path.parent() {
+            path.push(smv_dir);
+        }
+        path.push(filename);
+        path
+    }
+
     pub fn get_csv_vec_f64(
         &mut self,
         csv_type: String,
@@ -56,6 +338,29 @@ impl Outputs {
     }
 }
 
+fn new_f64_vec(
+    name: String,
+    x_name: &str,
+    x_units: &str,
+    y_name: &str,
+    y_units: &str,
+) -> DataVector<f64, f64> {
+    let mut vec = DataVector::new(
+        String::new(),
+        String::new(),
+        String::new(),
+        String::new(),
+        String::new(),
+        Vec::new(),
+    );
+    vec.name = name;
+    vec.x_name = x_name.to_string();
+    vec.x_units = x_units.to_string();
+    vec.y_name = y_name.to_string();
+    vec.y_units = y_units.to_string();
+    vec
+}
+
 fn take_f64_vec(
     vec: DataVector<f64, SmvValue>,
 ) -> Result<DataVector<f64, f64>, Box<dyn std::error::Error>> {
@@ -79,3 +384,189 @@ fn take_f64_vec(
     }
     Ok(new_dv)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write room_fire.smv to a new temporary directory, with its devices
+    /// split across two devc files.
+    fn write_test_outputs(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("red-smv-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let smv = format!(
+            "{}CSVF\n devc\n room_fire_1_devc.csv\n",
+            include_str!("room_fire.smv")
+        );
+        std::fs::write(dir.join("room_fire.smv"), smv).unwrap();
+        std::fs::write(
+            dir.join("room_fire_devc.csv"),
+            "s,C,kg/s\nTime,temp,burn\n0.0,20.0,0.0\n1.0,21.5,0.1\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("room_fire_1_devc.csv"),
+            "s,kW/m2\nTime,rad\n0.0,0.0\n1.0,2.5\n",
+        )
+        .unwrap();
+        dir
+    }
+
+    fn device_values(outputs: &mut Outputs, name: &str) -> Vec<(f64, f64)> {
+        let device = outputs.device(name).unwrap().expect("no device");
+        device.values.values().iter().map(|p| (p.x, p.y)).collect()
+    }
+
+    #[test]
+    fn device_in_numbered_devc_file() {
+        let dir = write_test_outputs("device");
+        let mut outputs = Outputs::from_file(dir.join("room_fire.smv")).unwrap();
+
+        let rad = outputs.device("rad").unwrap().expect("no device");
+        assert_eq!(rad.quantity, "RADIATIVE HEAT FLUX");
+        assert_eq!(rad.location, Xyz::new(2.55, 4.4, 0.75));
+        assert_eq!(rad.values.y_units, "kW/m2");
+        assert_eq!(
+            device_values(&mut outputs, "rad"),
+            vec![(0.0, 0.0), (1.0, 2.5)]
+        );
+
+        let temp = outputs.device("temp").unwrap().expect("no device");
+        assert_eq!(temp.values.y_units, "C");
+        assert!(outputs.device("nonexistent").unwrap().is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn open_slice() {
+        let dir = write_test_outputs("slice");
+        std::fs::write(
+            dir.join("room_fire_0001_01.sf"),
+            include_bytes!("room_fire_01.sf"),
+        )
+        .unwrap();
+        let outputs = Outputs::from_file(dir.join("room_fire.smv")).unwrap();
+        let slice = outputs
+            .slices()
+            .find(|slice| slice.quantity == "TEMPERATURE")
+            .expect("no slice");
+        assert_eq!(slice.slcf.filename, "room_fire_0001_01.sf");
+        assert_eq!(slice.units, "C");
+        assert_eq!(slice.mesh, 1);
+        let bounds = slice.bounds.expect("no bounds");
+        assert_eq!(bounds.x1, bounds.x2);
+        let mut parser = outputs.open_slice(slice.slcf).unwrap();
+        assert_eq!(parser.header.dimensions.j_max, 10);
+        assert!(parser.parse_frame().is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn diff_slice() {
+        let dir_a = write_test_outputs("diff_a");
+        let dir_b = write_test_outputs("diff_b");
+        let original = include_bytes!("room_fire_01.sf");
+        std::fs::write(dir_a.join("room_fire_0001_01.sf"), original).unwrap();
+        let slice = crate::parse_slice_file(&mut std::io::Cursor::new(original)).unwrap();
+        let mut writer = crate::SliceWriter::new(
+            std::fs::File::create(dir_b.join("room_fire_0001_01.sf")).unwrap(),
+            slice.header.clone(),
+        )
+        .unwrap();
+        for frame in &slice.frames {
+            let mut frame = frame.clone();
+            frame.values.iter_mut().for_each(|v| *v += 1.0);
+            writer.write_frame(&frame).unwrap();
+        }
+        drop(writer);
+        let a = Outputs::from_file(dir_a.join("room_fire.smv")).unwrap();
+        let b = Outputs::from_file(dir_b.join("room_fire.smv")).unwrap();
+        let diff = a.diff_slice(&a.smv.slcfs[0], &b, &b.smv.slcfs[0]).unwrap();
+        assert_eq!(diff.frames.len(), slice.frames.len());
+        for metrics in &diff.metrics {
+            assert!((metrics.max_abs - 1.0).abs() < 1e-3);
+            assert!((metrics.rms - 1.0).abs() < 1e-3);
+        }
+        assert!(a.diff_slice(&a.smv.slcfs[0], &b, &b.smv.slcfs[6]).is_err());
+        std::fs::remove_dir_all(&dir_a).unwrap();
+        std::fs::remove_dir_all(&dir_b).unwrap();
+    }
+
+    #[test]
+    fn probe_slice() {
+        let dir = write_test_outputs("probe");
+        std::fs::write(
+            dir.join("room_fire_0001_01.sf"),
+            include_bytes!("room_fire_01.sf"),
+        )
+        .unwrap();
+        let mut outputs = Outputs::from_file(dir.join("room_fire.smv")).unwrap();
+        let slcf = outputs.smv.slcfs[0].clone();
+        let mesh = outputs.smv.slice_mesh(&slcf).unwrap().clone();
+        let frames =
+            crate::parse_slice_file(&mut std::io::Cursor::new(include_bytes!("room_fire_01.sf")))
+                .unwrap()
+                .frames;
+        // Values of the cell-centred slice lie at cell centres, and j and k
+        // begin at 0 so index (j, k) is cell (j, k).
+        let x = mesh.trnx[14].f;
+        let centre = |trn: &[crate::TrnEntry], i: usize| (trn[i - 1].f + trn[i].f) / 2.0;
+        let (y3, y4, z5) = (
+            centre(&mesh.trny, 3),
+            centre(&mesh.trny, 4),
+            centre(&mesh.trnz, 5),
+        );
+        let at_centre = outputs
+            .probe(Xyz::new(x, y3, z5), "TEMPERATURE")
+            .unwrap()
+            .expect("no slice");
+        assert_eq!(at_centre.values().len(), frames.len());
+        assert_eq!(at_centre.y_units, "C");
+        let between = outputs
+            .probe(Xyz::new(x, (y3 + y4) / 2.0, z5), "TEMPERATURE")
+            .unwrap()
+            .expect("no slice");
+        for (n, frame) in frames.iter().enumerate() {
+            let v3 = frame.values[3 + 11 * 5] as f64;
+            let v4 = frame.values[4 + 11 * 5] as f64;
+            assert_eq!(at_centre.values()[n].x, frame.time as f64);
+            assert!((at_centre.values()[n].y - v3).abs() < 1e-9);
+            assert!((between.values()[n].y - (v3 + v4) / 2.0).abs() < 1e-6);
+        }
+        assert!(outputs
+            .probe(Xyz::new(-100.0, y3, z5), "TEMPERATURE")
+            .unwrap()
+            .is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn csv_cache_invalidation() {
+        let dir = write_test_outputs("cache");
+        let mut outputs = Outputs::from_file(dir.join("room_fire.smv")).unwrap();
+        assert_eq!(
+            device_values(&mut outputs, "rad"),
+            vec![(0.0, 0.0), (1.0, 2.5)]
+        );
+
+        // A change of length is found even if the modification time is
+        // unchanged.
+        let path = dir.join("room_fire_1_devc.csv");
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        std::fs::write(&path, "s,kW/m2\nTime,rad\n0.0,1.0\n").unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(modified).unwrap();
+        assert_eq!(device_values(&mut outputs, "rad"), vec![(0.0, 1.0)]);
+
+        // While the modification time and length are unchanged the cached
+        // block is used.
+        std::fs::write(&path, "s,kW/m2\nTime,rad\n0.0,2.0\n").unwrap();
+        file.set_modified(modified).unwrap();
+        assert_eq!(device_values(&mut outputs, "rad"), vec![(0.0, 1.0)]);
+
+        file.set_modified(modified + std::time::Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(device_values(&mut outputs, "rad"), vec![(0.0, 2.0)]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
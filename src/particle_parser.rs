@@ -0,0 +1,346 @@
+use crate::fortran_record::{next_frame, FrameOffsets};
+use crate::{RecordError, RecordFormat};
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Seek;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParticleFile {
+    pub header: ParticleHeader,
+    pub frames: Vec<ParticleFrame>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParticleHeader {
+    pub version: i32,
+    /// The particle classes written to the file, in the order they appear in
+    /// each frame.
+    pub classes: Vec<ParticleClass>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParticleClass {
+    pub quantities: Vec<ParticleQuantity>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParticleQuantity {
+    pub name: String,
+    pub units: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParticleFrame {
+    pub time: f32,
+    /// The particles of each class, in the same order as
+    /// [`ParticleHeader::classes`].
+    pub classes: Vec<ParticleClassFrame>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParticleClassFrame {
+    pub x: Vec<f32>,
+    pub y: Vec<f32>,
+    pub z: Vec<f32>,
+    /// The tag of each particle, which identifies it between frames.
+    pub tags: Vec<i32>,
+    /// The values of each quantity of the class for each particle, indexed as
+    /// `values[quantity][particle]`.
+    pub values: Vec<Vec<f32>>,
+}
+
+impl ParticleClassFrame {
+    pub fn n_particles(&self) -> usize {
+        self.tags.len()
+    }
+}
+
+#[derive(Debug)]
+pub enum ParseParticleError {
+    Record(RecordError),
+    /// The file does not start with the integer 1, which FDS writes at the
+    /// start of every particle file.
+    InvalidHeader,
+    /// A frame has a negative number of particles.
+    InvalidCount(i32),
+}
+
+impl std::fmt::Display for ParseParticleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Record(err) => write!(f, "could not read particle file: {}", err),
+            Self::InvalidHeader => write!(f, "particle file has an invalid header"),
+            Self::InvalidCount(n) => write!(f, "particle file has {} particles in a frame", n),
+        }
+    }
+}
+
+impl std::error::Error for ParseParticleError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            Self::Record(ref e) => Some(e),
+            Self::InvalidHeader | Self::InvalidCount(_) => None,
+        }
+    }
+}
+
+impl From<RecordError> for ParseParticleError {
+    fn from(err: RecordError) -> Self {
+        Self::Record(err)
+    }
+}
+
+/// A streaming reader for particle (.prt5) files. As the number of particles
+/// changes between frames, frames have no fixed length. The offsets of frames
+/// are therefore recorded as they are found when seeking.
+#[derive(Debug)]
+pub struct ParticleParser<R> {
+    reader: BufReader<R>,
+    pub header: ParticleHeader,
+    frame_offsets: FrameOffsets,
+    format: RecordFormat,
+}
+
+impl<R: Read> ParticleParser<R> {
+    pub fn parse_frame(&mut self) -> Result<ParticleFrame, ParseParticleError> {
+        parse_particle_frame(self.format, &self.header.classes, &mut self.reader)
+    }
+    pub fn header_length(&self) -> u64 {
+        self.frame_offsets.header_length()
+    }
+    /// The record format of the file, which is detected from the header.
+    pub fn format(&self) -> RecordFormat {
+        self.format
+    }
+}
+
+impl<R: Read + Seek> ParticleParser<R> {
+    pub fn new(input: R) -> Result<Self, ParseParticleError> {
+        let mut reader = BufReader::new(input);
+        let format = RecordFormat::detect(&mut reader).map_err(RecordError::IOError)?;
+        let header = parse_particle_header(format, &mut reader)?;
+        let header_length = reader.stream_position().map_err(RecordError::IOError)?;
+        Ok(ParticleParser {
+            reader,
+            header,
+            frame_offsets: FrameOffsets::new(header_length),
+            format,
+        })
+    }
+    /// Seek to the start of a frame, returning its offset.
+    pub fn seek_frame(&mut self, frame: usize) -> Result<u64, ParseParticleError> {
+        let format = self.format;
+        let classes = &self.header.classes;
+        self.frame_offsets.seek(&mut self.reader, frame, |reader| {
+            skip_particle_frame(format, classes, reader)
+        })
+    }
+    pub fn get_frame(&mut self, frame: usize) -> Result<ParticleFrame, ParseParticleError> {
+        self.seek_frame(frame)?;
+        self.parse_frame()
+    }
+}
+
+impl<R: Read> Iterator for ParticleParser<R> {
+    type Item = Result<ParticleFrame, ParseParticleError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let format = self.format;
+        let classes = &self.header.classes;
+        next_frame(&mut self.reader, |reader| {
+            parse_particle_frame(format, classes, reader)
+        })
+    }
+}
+
+pub fn parse_particle_file<R: Read + Seek>(i: &mut R) -> Result<ParticleFile, ParseParticleError> {
+    let parser = ParticleParser::new(i)?;
+    let header = parser.header.clone();
+    let frames = parser.collect::<Result<Vec<_>, _>>()?;
+    Ok(ParticleFile { header, frames })
+}
+
+pub fn parse_particle_frame<R: Read>(
+    format: RecordFormat,
+    classes: &[ParticleClass],
+    mut i: R,
+) -> Result<ParticleFrame, ParseParticleError> {
+    let time = format.decode_f32s(&format.read_record_expecting(&mut i, 4)?)?;
+    let mut class_frames = Vec::with_capacity(classes.len());
+    for class in classes {
+        let n = parse_n_particles(format, &mut i)?;
+        let position_length = 3 * n as u64 * 4;
+        let mut positions =
+            format.decode_f32s(&format.read_record_expecting(&mut i, position_length)?)?;
+        let z = positions.split_off(2 * n);
+        let y = positions.split_off(n);
+        let x = positions;
+        let tags = format.decode_i32s(&format.read_record_expecting(&mut i, n as u64 * 4)?)?;
+        let n_quantities = class.quantities.len();
+        let values = if n_quantities > 0 {
+            let value_length = (n * n_quantities) as u64 * 4;
+            let values =
+                format.decode_f32s(&format.read_record_expecting(&mut i, value_length)?)?;
+            if n > 0 {
+                values.chunks_exact(n).map(|v| v.to_vec()).collect()
+            } else {
+                vec![vec![]; n_quantities]
+            }
+        } else {
+            vec![]
+        };
+        class_frames.push(ParticleClassFrame {
+            x,
+            y,
+            z,
+            tags,
+            values,
+        });
+    }
+    Ok(ParticleFrame {
+        time: time[0],
+        classes: class_frames,
+    })
+}
+
+/// Skip over a frame without reading the particle data.
+fn skip_particle_frame<R: Read + Seek>(
+    format: RecordFormat,
+    classes: &[ParticleClass],
+    i: &mut BufReader<R>,
+) -> Result<(), ParseParticleError> {
+    i.seek_relative(format.record_length(4) as i64)
+        .map_err(RecordError::IOError)?;
+    for class in classes {
+        let n = parse_n_particles(format, i)? as u64;
+        let position_length = format.record_length(3 * n * 4);
+        let tag_length = format.record_length(n * 4);
+        let n_quantities = class.quantities.len() as u64;
+        let value_length = if n_quantities > 0 {
+            format.record_length(n * n_quantities * 4)
+        } else {
+            0
+        };
+        i.seek_relative((position_length + tag_length + value_length) as i64)
+            .map_err(RecordError::IOError)?;
+    }
+    Ok(())
+}
+
+fn parse_n_particles<R: Read>(
+    format: RecordFormat,
+    i: &mut R,
+) -> Result<usize, ParseParticleError> {
+    let values = format.decode_i32s(&format.read_record_expecting(i, 4)?)?;
+    match values[0] {
+        n if n >= 0 => Ok(n as usize),
+        n => Err(ParseParticleError::InvalidCount(n)),
+    }
+}
+
+fn parse_particle_header<R: Read>(
+    format: RecordFormat,
+    i: &mut R,
+) -> Result<ParticleHeader, ParseParticleError> {
+    if format.decode_i32s(&format.read_record(i)?)? != [1] {
+        return Err(ParseParticleError::InvalidHeader);
+    }
+    let version = format.decode_i32s(&format.read_record_expecting(i, 4)?)?[0];
+    let n_classes = format.decode_i32s(&format.read_record_expecting(i, 4)?)?[0];
+    let mut classes = Vec::new();
+    for _ in 0..n_classes {
+        // The number of quantities is followed by an unused value.
+        let n_quantities = format.decode_i32s(&format.read_record_expecting(i, 8)?)?[0];
+        let mut quantities = Vec::new();
+        for _ in 0..n_quantities {
+            let name = format.read_record(i)?;
+            let units = format.read_record(i)?;
+            quantities.push(ParticleQuantity {
+                name: String::from_utf8_lossy(&name).trim().to_string(),
+                units: String::from_utf8_lossy(&units).trim().to_string(),
+            });
+        }
+        classes.push(ParticleClass { quantities });
+    }
+    Ok(ParticleHeader { version, classes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fortran_record::test_records::{formats, RecordWriter};
+
+    /// Build a particle file with two classes, the first with no quantities
+    /// and the second with two, where frame `n` has `n` particles of each
+    /// class.
+    fn make_particle_file(format: RecordFormat, n_frames: usize) -> Vec<u8> {
+        let mut out = RecordWriter::new(format);
+        out.i32s(&[1]).i32s(&[5]).i32s(&[2]);
+        out.i32s(&[0, 0]).i32s(&[2, 0]);
+        for s in &["PARTICLE DIAMETER", "mu-m", "PARTICLE VELOCITY", "m/s"] {
+            out.label(s);
+        }
+        for frame in 0..n_frames {
+            let n = frame as i32;
+            out.f32s(&[frame as f32 * 0.5]);
+            for class in 0..2 {
+                let positions: Vec<f32> = (0..3 * n).map(|v| v as f32).collect();
+                let tags: Vec<i32> = (0..n).map(|v| class * 100 + v).collect();
+                out.i32s(&[n]).f32s(&positions).i32s(&tags);
+                if class == 1 {
+                    let values: Vec<f32> = (0..2 * n).map(|v| -v as f32).collect();
+                    out.f32s(&values);
+                }
+            }
+        }
+        out.data
+    }
+
+    #[test]
+    fn parse_particle_simple() {
+        let data = make_particle_file(RecordFormat::default(), 4);
+        let result = parse_particle_file(&mut std::io::Cursor::new(&data)).unwrap();
+        assert_eq!(result.header.version, 5);
+        assert_eq!(result.header.classes.len(), 2);
+        assert!(result.header.classes[0].quantities.is_empty());
+        assert_eq!(
+            result.header.classes[1].quantities[1],
+            ParticleQuantity {
+                name: "PARTICLE VELOCITY".to_string(),
+                units: "m/s".to_string(),
+            }
+        );
+        assert_eq!(result.frames.len(), 4);
+        let frame = &result.frames[2];
+        assert_eq!(frame.time, 1.0);
+        assert_eq!(frame.classes[0].n_particles(), 2);
+        assert_eq!(frame.classes[0].x, vec![0., 1.]);
+        assert_eq!(frame.classes[0].y, vec![2., 3.]);
+        assert_eq!(frame.classes[0].z, vec![4., 5.]);
+        assert!(frame.classes[0].values.is_empty());
+        assert_eq!(frame.classes[1].tags, vec![100, 101]);
+        assert_eq!(frame.classes[1].values, vec![vec![0., -1.], vec![-2., -3.]]);
+        assert_eq!(result.frames[0].classes[1].values, vec![vec![], vec![]]);
+
+        let mut parser = ParticleParser::new(std::io::Cursor::new(&data)).unwrap();
+        for i in [3, 1, 2, 0].iter() {
+            assert_eq!(parser.get_frame(*i).unwrap(), result.frames[*i]);
+        }
+        assert!(parser.get_frame(4).is_err());
+
+        let format = formats()[1];
+        let data = make_particle_file(format, 4);
+        let mut parser = ParticleParser::new(std::io::Cursor::new(&data)).unwrap();
+        assert_eq!(parser.format(), format);
+        assert_eq!(parser.header, result.header);
+        assert_eq!(parser.get_frame(3).unwrap(), result.frames[3]);
+    }
+
+    #[test]
+    fn parse_particle_bad_header() {
+        let mut data = make_particle_file(RecordFormat::default(), 1);
+        data[4..8].copy_from_slice(&16777216_i32.to_le_bytes());
+        let result = parse_particle_file(&mut std::io::Cursor::new(&data));
+        assert!(matches!(result, Err(ParseParticleError::InvalidHeader)));
+    }
+}
@@ -0,0 +1,307 @@
+use crate::{RecordError, RecordFormat, SmvMesh, Xyz, PL3D_QUANTITIES};
+use std::io::BufReader;
+use std::io::Read;
+
+/// The grid (.xyz) file of a mesh, which holds the coordinates of each grid
+/// node. Nodes are indexed from 0 along each axis, in the same way as the
+/// TRN entries of [`SmvMesh`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plot3dGrid {
+    pub i_dim: usize,
+    pub j_dim: usize,
+    pub k_dim: usize,
+    pub x: Vec<f32>,
+    pub y: Vec<f32>,
+    pub z: Vec<f32>,
+    /// Whether each node is blanked by an obstruction, if written.
+    pub iblank: Option<Vec<i32>>,
+}
+
+impl Plot3dGrid {
+    /// The index of a node in the arrays, with i varying fastest.
+    pub fn index(&self, i: usize, j: usize, k: usize) -> Option<usize> {
+        node_index((self.i_dim, self.j_dim, self.k_dim), i, j, k)
+    }
+    pub fn xyz(&self, i: usize, j: usize, k: usize) -> Option<Xyz> {
+        let n = self.index(i, j, k)?;
+        Some(Xyz::new(
+            self.x[n] as f64,
+            self.y[n] as f64,
+            self.z[n] as f64,
+        ))
+    }
+}
+
+/// A PLOT3D solution (.q) file, which holds the values of the quantities at
+/// each grid node of a mesh at a single time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plot3dSolution {
+    pub i_dim: usize,
+    pub j_dim: usize,
+    pub k_dim: usize,
+    /// The Mach number, angle of attack, Reynolds number and time of the
+    /// PLOT3D format. FDS writes these as zero.
+    pub parameters: [f32; 4],
+    /// The values of each quantity in turn, with i varying fastest.
+    pub values: Vec<f32>,
+}
+
+impl Plot3dSolution {
+    /// The index of a node in the values of a quantity, with i varying
+    /// fastest.
+    pub fn index(&self, i: usize, j: usize, k: usize) -> Option<usize> {
+        node_index((self.i_dim, self.j_dim, self.k_dim), i, j, k)
+    }
+    pub fn n_nodes(&self) -> usize {
+        self.i_dim * self.j_dim * self.k_dim
+    }
+    /// The values of the quantity with the given index, in the order given
+    /// by [`crate::Pl3d::quantities`].
+    pub fn quantity(&self, quantity: usize) -> Option<&[f32]> {
+        let n = self.n_nodes();
+        let start = quantity.checked_mul(n)?;
+        self.values.get(start..start.checked_add(n)?)
+    }
+    pub fn value(&self, quantity: usize, i: usize, j: usize, k: usize) -> Option<f32> {
+        let n = self.index(i, j, k)?;
+        self.quantity(quantity)?.get(n).copied()
+    }
+    /// Check that the solution has a value at each grid node of the mesh.
+    pub fn matches_mesh(&self, mesh: &SmvMesh) -> bool {
+        self.i_dim as u64 == mesh.i_bar + 1
+            && self.j_dim as u64 == mesh.j_bar + 1
+            && self.k_dim as u64 == mesh.k_bar + 1
+    }
+}
+
+fn node_index(dims: (usize, usize, usize), i: usize, j: usize, k: usize) -> Option<usize> {
+    let (i_dim, j_dim, k_dim) = dims;
+    if i < i_dim && j < j_dim && k < k_dim {
+        Some(i + i_dim * (j + j_dim * k))
+    } else {
+        None
+    }
+}
+
+#[derive(Debug)]
+pub enum ParsePlot3dError {
+    Record(RecordError),
+    /// The dimensions of the grid are negative.
+    InvalidDimensions([i32; 3]),
+}
+
+impl std::fmt::Display for ParsePlot3dError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Record(err) => write!(f, "could not read PLOT3D file: {}", err),
+            Self::InvalidDimensions(dims) => {
+                write!(f, "PLOT3D file has invalid dimensions {:?}", dims)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParsePlot3dError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            Self::Record(ref e) => Some(e),
+            Self::InvalidDimensions(_) => None,
+        }
+    }
+}
+
+impl From<RecordError> for ParsePlot3dError {
+    fn from(err: RecordError) -> Self {
+        Self::Record(err)
+    }
+}
+
+/// Parse a grid (.xyz) file. This holds the dimensions, followed by a single
+/// record with the x, y and z coordinates of every node and optionally the
+/// iblank values. The record format is detected from the dimensions.
+pub fn parse_plot3d_grid<R: Read>(i: R) -> Result<Plot3dGrid, ParsePlot3dError> {
+    let mut i = BufReader::new(i);
+    let format = RecordFormat::detect(&mut i).map_err(RecordError::IOError)?;
+    let dims = parse_plot3d_dimensions(format, &mut i)?;
+    let (i_dim, j_dim, k_dim) = dims;
+    let record = format.read_record(&mut i)?;
+    let (coords_length, iblank) = match (values_length(dims, 3), values_length(dims, 4)) {
+        (Some(coords_length), Some(length)) if record.len() == length => (
+            coords_length,
+            Some(format.decode_i32s(&record[coords_length..])?),
+        ),
+        (Some(coords_length), _) if record.len() == coords_length => (coords_length, None),
+        (coords_length, _) => return Err(values_length_error(coords_length, record.len())),
+    };
+    let n = coords_length / 3 / 4;
+    let mut coords = format.decode_f32s(&record[..coords_length])?;
+    let z = coords.split_off(2 * n);
+    let y = coords.split_off(n);
+    let x = coords;
+    Ok(Plot3dGrid {
+        i_dim,
+        j_dim,
+        k_dim,
+        x,
+        y,
+        z,
+        iblank,
+    })
+}
+
+/// Parse a PLOT3D solution (.q) file. This holds the dimensions, the four
+/// PLOT3D parameters, then a single record with the values of each quantity.
+/// The record format is detected from the dimensions.
+pub fn parse_plot3d_solution<R: Read>(i: R) -> Result<Plot3dSolution, ParsePlot3dError> {
+    let mut i = BufReader::new(i);
+    let format = RecordFormat::detect(&mut i).map_err(RecordError::IOError)?;
+    let (i_dim, j_dim, k_dim) = parse_plot3d_dimensions(format, &mut i)?;
+    let parameters = format.decode_f32s(&format.read_record_expecting(&mut i, 4 * 4)?)?;
+    let parameters = [parameters[0], parameters[1], parameters[2], parameters[3]];
+    let record = format.read_record(&mut i)?;
+    let length = values_length((i_dim, j_dim, k_dim), PL3D_QUANTITIES);
+    if length != Some(record.len()) {
+        return Err(values_length_error(length, record.len()));
+    }
+    Ok(Plot3dSolution {
+        i_dim,
+        j_dim,
+        k_dim,
+        parameters,
+        values: format.decode_f32s(&record)?,
+    })
+}
+
+/// The length in bytes of a record with the given number of values at each
+/// grid node, or `None` if this is too large for any record to hold.
+fn values_length(dims: (usize, usize, usize), values_per_node: usize) -> Option<usize> {
+    let (i_dim, j_dim, k_dim) = dims;
+    i_dim
+        .checked_mul(j_dim)?
+        .checked_mul(k_dim)?
+        .checked_mul(values_per_node)?
+        .checked_mul(4)
+}
+
+/// The error for a record which does not have the length of the values at
+/// each grid node. If that length is too large for any record to hold, the
+/// expected length is given as [`u64::MAX`].
+fn values_length_error(expected: Option<usize>, found: usize) -> ParsePlot3dError {
+    RecordError::RecLengthError {
+        expected: expected.map_or(u64::MAX, |length| length as u64),
+        found: found as u64,
+    }
+    .into()
+}
+
+fn parse_plot3d_dimensions<R: Read>(
+    format: RecordFormat,
+    i: &mut R,
+) -> Result<(usize, usize, usize), ParsePlot3dError> {
+    let record = format.read_record_expecting(i, 3 * 4)?;
+    match format.decode_i32s(&record)?.as_slice() {
+        [i_dim, j_dim, k_dim] if *i_dim >= 0 && *j_dim >= 0 && *k_dim >= 0 => {
+            Ok((*i_dim as usize, *j_dim as usize, *k_dim as usize))
+        }
+        dims => Err(ParsePlot3dError::InvalidDimensions([
+            dims[0], dims[1], dims[2],
+        ])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fortran_record::test_records::{formats, RecordWriter};
+
+    /// Build a grid file for a 2x3x2 node grid with 0.5 m cells.
+    fn make_grid_file(format: RecordFormat) -> Vec<u8> {
+        let mut values = Vec::new();
+        for axis in 0..3 {
+            for k in 0..2 {
+                for j in 0..3 {
+                    for i in 0..2 {
+                        values.push([i, j, k][axis] as f32 * 0.5);
+                    }
+                }
+            }
+        }
+        let mut record = format.encode_f32s(&values);
+        record.extend(format.encode_i32s(&[0; 12]));
+        let mut data = RecordWriter::new(format);
+        data.i32s(&[2, 3, 2]).bytes(&record);
+        data.data
+    }
+
+    /// Build a solution file for a 2x3x2 node grid.
+    fn make_solution_file(format: RecordFormat, values: &[f32]) -> Vec<u8> {
+        let mut data = RecordWriter::new(format);
+        data.i32s(&[2, 3, 2]).f32s(&[0.; 4]).f32s(values);
+        data.data
+    }
+
+    #[test]
+    fn parse_plot3d_grid_simple() {
+        for format in formats().iter() {
+            let data = make_grid_file(*format);
+            let grid = parse_plot3d_grid(std::io::Cursor::new(&data)).unwrap();
+            assert_eq!((grid.i_dim, grid.j_dim, grid.k_dim), (2, 3, 2));
+            assert_eq!(grid.xyz(1, 2, 1), Some(Xyz::new(0.5, 1.0, 0.5)));
+            assert_eq!(grid.xyz(2, 0, 0), None);
+            assert_eq!(grid.iblank, Some(vec![0; 12]));
+        }
+    }
+
+    #[test]
+    fn parse_plot3d_solution_simple() {
+        let values: Vec<f32> = (0..12 * PL3D_QUANTITIES).map(|v| v as f32).collect();
+        for format in formats().iter() {
+            let data = make_solution_file(*format, &values);
+            let solution = parse_plot3d_solution(std::io::Cursor::new(&data)).unwrap();
+            assert_eq!(solution.n_nodes(), 12);
+            assert_eq!(solution.value(0, 1, 0, 0), Some(1.));
+            assert_eq!(solution.value(2, 1, 2, 1), Some(35.));
+            assert_eq!(solution.value(5, 0, 0, 0), None);
+            assert_eq!(solution.quantity(usize::MAX), None);
+
+            let data = make_solution_file(*format, &values[1..]);
+            let err = parse_plot3d_solution(std::io::Cursor::new(&data)).unwrap_err();
+            assert!(matches!(
+                err,
+                ParsePlot3dError::Record(RecordError::RecLengthError {
+                    expected: 240,
+                    found: 236,
+                })
+            ));
+            assert_eq!(
+                err.to_string(),
+                "could not read PLOT3D file: record has length 236 but 240 was expected"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_plot3d_overflowing_dimensions() {
+        let max = i32::MAX;
+        let mut data = RecordWriter::new(RecordFormat::default());
+        data.i32s(&[max, max, max]).bytes(&[0; 4 * 4]).bytes(&[]);
+        let data = data.data;
+        // The grid file has no parameter record, so the grid is read from it.
+        let result = parse_plot3d_grid(std::io::Cursor::new(&data));
+        assert!(matches!(
+            result,
+            Err(ParsePlot3dError::Record(RecordError::RecLengthError {
+                expected: u64::MAX,
+                found: 16,
+            }))
+        ));
+        let result = parse_plot3d_solution(std::io::Cursor::new(&data));
+        assert!(matches!(
+            result,
+            Err(ParsePlot3dError::Record(RecordError::RecLengthError {
+                expected: u64::MAX,
+                found: 0,
+            }))
+        ));
+    }
+}
@@ -0,0 +1,125 @@
+use crate::{Frame, GridCoord, GridRegion, Slcf, SmvMesh, TrnEntry, Xb, Xyz};
+use std::convert::TryFrom;
+
+/// The distance (m) a point may be outside a slice and still be considered
+/// inside it. This allows for the rounding of coordinates in the SMV file.
+pub(crate) const TOLERANCE: f64 = 1e-4;
+
+/// Interpolates the values of a slice at a fixed point. The weights are
+/// calculated once so that values can be taken cheaply from every frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SliceProbe {
+    /// The index of each value used and its weight.
+    weights: Vec<(usize, f64)>,
+}
+
+impl SliceProbe {
+    /// Create a probe at a point within a slice of the given mesh. Returns
+    /// None if the point is not within the slice.
+    pub fn new(mesh: &SmvMesh, slcf: &Slcf, xyz: Xyz) -> Option<Self> {
+        let bounds = mesh.xb_from_grid(slcf.ijk)?;
+        if !contains(&bounds, xyz) {
+            return None;
+        }
+        let [xs, ys, zs] = value_positions(mesh, &slcf.ijk, slcf.cell_centred)?;
+        let (i_dim, j_dim) = (xs.len(), ys.len());
+        let mut weights = Vec::with_capacity(8);
+        for (i, wx) in axis_weights(&xs, xyz.x) {
+            for (j, wy) in axis_weights(&ys, xyz.y) {
+                for (k, wz) in axis_weights(&zs, xyz.z) {
+                    let w = wx * wy * wz;
+                    if w > 0.0 {
+                        weights.push((i + i_dim * (j + j_dim * k), w));
+                    }
+                }
+            }
+        }
+        Some(Self { weights })
+    }
+
+    /// The interpolated value in a frame. Returns None if the frame is
+    /// smaller than the slice the probe was created for.
+    pub fn value(&self, frame: &Frame) -> Option<f64> {
+        self.weights.iter().try_fold(0.0, |total, (n, w)| {
+            Some(total + (*frame.values.get(*n)? as f64) * w)
+        })
+    }
+}
+
+/// The positions along each axis of the values of a slice covering `ijk`. The
+/// values of cell-centred slices lie at the centres of cells, otherwise they
+/// lie on the grid lines.
+pub(crate) fn value_positions(
+    mesh: &SmvMesh,
+    ijk: &GridRegion,
+    cell_centred: bool,
+) -> Option<[Vec<f64>; 3]> {
+    let positions = |trn: &[TrnEntry], min: GridCoord, max: GridCoord| -> Option<Vec<f64>> {
+        (min..=max)
+            .map(|i| {
+                let i = usize::try_from(i).ok()?;
+                if cell_centred {
+                    cell_centre(trn, i)
+                } else {
+                    trn.get(i).map(|entry| entry.f)
+                }
+            })
+            .collect()
+    };
+    Some([
+        positions(&mesh.trnx, ijk.i1, ijk.i2)?,
+        positions(&mesh.trny, ijk.j1, ijk.j2)?,
+        positions(&mesh.trnz, ijk.k1, ijk.k2)?,
+    ])
+}
+
+pub(crate) fn contains(xb: &Xb, xyz: Xyz) -> bool {
+    let within = |a: f64, b: f64, v: f64| v >= a.min(b) - TOLERANCE && v <= a.max(b) + TOLERANCE;
+    within(xb.x1, xb.x2, xyz.x) && within(xb.y1, xb.y2, xyz.y) && within(xb.z1, xb.z2, xyz.z)
+}
+
+/// The centre of cell `i`, which lies between grid lines `i - 1` and `i`.
+/// Cell 0 is outside the mesh and is taken to be the same size as cell 1.
+fn cell_centre(trn: &[TrnEntry], i: usize) -> Option<f64> {
+    if i == 0 {
+        let (a, b) = (trn.first()?.f, trn.get(1)?.f);
+        Some(a - (b - a) / 2.0)
+    } else {
+        Some((trn.get(i - 1)?.f + trn.get(i)?.f) / 2.0)
+    }
+}
+
+/// The indices and weights for linear interpolation of `v` between the
+/// positions along an axis. Values beyond the first or last position take
+/// the value at that position.
+fn axis_weights(positions: &[f64], v: f64) -> Vec<(usize, f64)> {
+    let last = positions.len().saturating_sub(1);
+    if positions.len() < 2 || v <= positions[0] {
+        return vec![(0, 1.0)];
+    }
+    if v >= positions[last] {
+        return vec![(last, 1.0)];
+    }
+    for (n, pair) in positions.windows(2).enumerate() {
+        if v <= pair[1] {
+            let w = (v - pair[0]) / (pair[1] - pair[0]);
+            return vec![(n, 1.0 - w), (n + 1, w)];
+        }
+    }
+    vec![(last, 1.0)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_weights() {
+        let positions = [0.0, 1.0, 3.0];
+        assert_eq!(axis_weights(&positions, -1.0), vec![(0, 1.0)]);
+        assert_eq!(axis_weights(&positions, 0.5), vec![(0, 0.5), (1, 0.5)]);
+        assert_eq!(axis_weights(&positions, 2.5), vec![(1, 0.25), (2, 0.75)]);
+        assert_eq!(axis_weights(&positions, 3.0), vec![(2, 1.0)]);
+        assert_eq!(axis_weights(&[2.0], 5.0), vec![(0, 1.0)]);
+    }
+}
@@ -0,0 +1,324 @@
+use crate::probe::{value_positions, TOLERANCE};
+use crate::{Axis, Frame, GridRegion, ParseSliceError, SliceHeader, SliceParser, SmvMesh};
+use std::io::{Read, Seek};
+
+/// The difference between two slices of the same quantity on the same grid,
+/// e.g. from two runs of a sensitivity study.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SliceDiff {
+    /// The header of the first slice. The difference has the same quantity
+    /// and units, so it can be written with [`crate::SliceWriter`].
+    pub header: SliceHeader,
+    /// The values of the first slice minus those of the second, at the times
+    /// of the frames of the first slice.
+    pub frames: Vec<Frame>,
+    /// The metrics of each frame of the difference.
+    pub metrics: Vec<DiffMetrics>,
+}
+
+/// Summary metrics of a frame of a [`SliceDiff`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DiffMetrics {
+    pub time: f32,
+    /// The largest absolute difference.
+    pub max_abs: f32,
+    /// The root mean square of the differences.
+    pub rms: f64,
+}
+
+impl DiffMetrics {
+    fn new(frame: &Frame) -> Self {
+        let max_abs = frame.values.iter().fold(0.0_f32, |max, v| max.max(v.abs()));
+        let sum_sq: f64 = frame.values.iter().map(|v| (*v as f64).powi(2)).sum();
+        let rms = if frame.values.is_empty() {
+            0.0
+        } else {
+            (sum_sq / frame.values.len() as f64).sqrt()
+        };
+        Self {
+            time: frame.time,
+            max_abs,
+            rms,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SliceDiffError {
+    Slice(ParseSliceError),
+    /// The slices have different quantities or units.
+    Quantity {
+        a: (String, String),
+        b: (String, String),
+    },
+    /// The slices have different dimensions.
+    Dimensions {
+        a: crate::Dimensions,
+        b: crate::Dimensions,
+    },
+    /// One slice is cell-centred and the other is not.
+    CellCentred,
+    /// A slice does not lie on the grid of its mesh.
+    OutsideMesh,
+    /// The positions of the values differ along an axis. `index` is the
+    /// position of the first value that differs along that axis.
+    Grid {
+        axis: Axis,
+        index: usize,
+    },
+}
+
+impl std::fmt::Display for SliceDiffError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Slice(err) => write!(f, "{}", err),
+            Self::Quantity { a, b } => {
+                write!(f, "cannot compare {} ({}) with {} ({})", a.0, a.1, b.0, b.1)
+            }
+            Self::Dimensions { a, b } => write!(
+                f,
+                "slice dimensions {:?} and {:?} differ",
+                a.shape(),
+                b.shape()
+            ),
+            Self::CellCentred => write!(
+                f,
+                "cannot compare a cell-centred slice with a node-centred slice"
+            ),
+            Self::OutsideMesh => write!(f, "slice does not lie on the grid of its mesh"),
+            Self::Grid { axis, index } => write!(
+                f,
+                "slice grids differ along the {:?} axis at position {}",
+                axis, index
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SliceDiffError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Slice(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseSliceError> for SliceDiffError {
+    fn from(err: ParseSliceError) -> Self {
+        Self::Slice(err)
+    }
+}
+
+/// Check that two slices have the same quantity, units and dimensions.
+pub fn check_slice_headers(a: &SliceHeader, b: &SliceHeader) -> Result<(), SliceDiffError> {
+    let quantity = |header: &SliceHeader| {
+        (
+            header.quantity.trim().to_string(),
+            header.units.trim().to_string(),
+        )
+    };
+    if quantity(a) != quantity(b) {
+        return Err(SliceDiffError::Quantity {
+            a: quantity(a),
+            b: quantity(b),
+        });
+    }
+    if a.dimensions != b.dimensions {
+        return Err(SliceDiffError::Dimensions {
+            a: a.dimensions,
+            b: b.dimensions,
+        });
+    }
+    Ok(())
+}
+
+/// Check that the values of a slice covering `ijk` lie at the same positions
+/// in two meshes, e.g. the same mesh in two runs.
+pub fn check_slice_grids(
+    a: &SmvMesh,
+    b: &SmvMesh,
+    ijk: &GridRegion,
+    cell_centred: bool,
+) -> Result<(), SliceDiffError> {
+    let a = value_positions(a, ijk, cell_centred).ok_or(SliceDiffError::OutsideMesh)?;
+    let b = value_positions(b, ijk, cell_centred).ok_or(SliceDiffError::OutsideMesh)?;
+    for ((axis, a), b) in [Axis::X, Axis::Y, Axis::Z].iter().zip(&a).zip(&b) {
+        if let Some(index) = a
+            .iter()
+            .zip(b)
+            .position(|(a, b)| (a - b).abs() > TOLERANCE)
+        {
+            return Err(SliceDiffError::Grid { axis: *axis, index });
+        }
+    }
+    Ok(())
+}
+
+/// Subtract the frames of slice `b` from those of slice `a`, which must have
+/// the same quantity, units and dimensions. Slice files do not hold the
+/// coordinates of the grid, so these should be checked with
+/// [`check_slice_grids`] or by using [`crate::Outputs::diff_slice`].
+///
+/// The difference is taken at the time of each frame of `a`. Where `b` has
+/// no frame at that time it is interpolated linearly between the frames
+/// either side. Frames of `a` outside the times of `b` are skipped.
+pub fn diff_slices<A, B>(a: SliceParser<A>, b: SliceParser<B>) -> Result<SliceDiff, SliceDiffError>
+where
+    A: Read + Seek,
+    B: Read + Seek,
+{
+    check_slice_headers(&a.header, &b.header)?;
+    let header = a.header.clone();
+    let mut b = b.peekable();
+    let mut previous: Option<Frame> = None;
+    let mut frames = Vec::new();
+    let mut metrics = Vec::new();
+    for frame in a {
+        let frame = frame?;
+        // Advance `b` so that `previous` is the last frame at or before the
+        // time of this frame, and the next frame of `b` is after it.
+        while let Some(next) = b.peek() {
+            match next {
+                Ok(next) if next.time > frame.time => break,
+                _ => (),
+            }
+            previous = b.next().transpose()?;
+        }
+        let values = match (&previous, b.peek()) {
+            (Some(before), _) if before.time == frame.time => before.values.clone(),
+            (Some(before), Some(Ok(after))) => {
+                let w = (frame.time - before.time) / (after.time - before.time);
+                before
+                    .values
+                    .iter()
+                    .zip(&after.values)
+                    .map(|(v0, v1)| v0 + (v1 - v0) * w)
+                    .collect()
+            }
+            _ => continue,
+        };
+        let diff = Frame {
+            time: frame.time,
+            values: frame
+                .values
+                .iter()
+                .zip(&values)
+                .map(|(a, b)| a - b)
+                .collect(),
+        };
+        metrics.push(DiffMetrics::new(&diff));
+        frames.push(diff);
+    }
+    Ok(SliceDiff {
+        header,
+        frames,
+        metrics,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_smv_file, Dimensions, SliceWriter};
+
+    fn write_slice(header: &SliceHeader, frames: &[(f32, Vec<f32>)]) -> Vec<u8> {
+        let mut writer = SliceWriter::new(Vec::new(), header.clone()).unwrap();
+        for (time, values) in frames {
+            writer
+                .write_frame(&Frame {
+                    time: *time,
+                    values: values.clone(),
+                })
+                .unwrap();
+        }
+        writer.into_inner()
+    }
+
+    fn test_header(units: &str) -> SliceHeader {
+        SliceHeader {
+            quantity: "TEMPERATURE".to_string(),
+            short_name: "temp".to_string(),
+            units: units.to_string(),
+            dimensions: Dimensions {
+                i_min: 0,
+                i_max: 1,
+                j_min: 2,
+                j_max: 2,
+                k_min: 0,
+                k_max: 0,
+            },
+        }
+    }
+
+    fn parser(data: Vec<u8>) -> SliceParser<std::io::Cursor<Vec<u8>>> {
+        SliceParser::new(std::io::Cursor::new(data)).unwrap()
+    }
+
+    #[test]
+    fn diff_interpolated() {
+        let header = test_header("C");
+        let a = write_slice(
+            &header,
+            &[
+                (0.0, vec![20., 20.]),
+                (1.0, vec![30., 24.]),
+                (2.5, vec![40., 28.]),
+                (5.0, vec![50., 30.]),
+            ],
+        );
+        let b = write_slice(
+            &header,
+            &[
+                (0.0, vec![20., 20.]),
+                (2.0, vec![30., 20.]),
+                (3.0, vec![40., 30.]),
+            ],
+        );
+        let diff = diff_slices(parser(a), parser(b)).unwrap();
+        let times: Vec<f32> = diff.frames.iter().map(|frame| frame.time).collect();
+        assert_eq!(times, vec![0.0, 1.0, 2.5]);
+        assert_eq!(diff.frames[1].values, vec![5., 4.]);
+        assert_eq!(diff.frames[2].values, vec![5., 3.]);
+        assert_eq!(
+            diff.metrics[2],
+            DiffMetrics {
+                time: 2.5,
+                max_abs: 5.,
+                rms: 17.0_f64.sqrt(),
+            }
+        );
+        assert_eq!(diff.metrics[0].max_abs, 0.0);
+    }
+
+    #[test]
+    fn diff_incompatible() {
+        let a = write_slice(&test_header("C"), &[]);
+        let b = write_slice(&test_header("K"), &[]);
+        assert!(matches!(
+            diff_slices(parser(a.clone()), parser(b)),
+            Err(SliceDiffError::Quantity { .. })
+        ));
+        let mut header = test_header("C");
+        header.dimensions.i_max = 2;
+        let b = write_slice(&header, &[]);
+        assert!(matches!(
+            diff_slices(parser(a), parser(b)),
+            Err(SliceDiffError::Dimensions { .. })
+        ));
+
+        let smv = parse_smv_file(std::io::Cursor::new(include_str!("room_fire.smv"))).unwrap();
+        let slcf = &smv.slcfs[0];
+        let mesh = smv.slice_mesh(slcf).unwrap();
+        assert!(check_slice_grids(mesh, mesh, &slcf.ijk, slcf.cell_centred).is_ok());
+        let mut moved = mesh.clone();
+        moved.trny[3].f += 0.05;
+        assert!(matches!(
+            check_slice_grids(mesh, &moved, &slcf.ijk, slcf.cell_centred),
+            Err(SliceDiffError::Grid {
+                axis: Axis::Y,
+                index: 3
+            })
+        ));
+    }
+}
@@ -0,0 +1,178 @@
+use crate::{Endianness, Frame, ParseSliceError, RecordFormat, SliceHeader, SliceParser};
+use memmap2::Mmap;
+use std::path::Path;
+
+/// A slice file mapped into memory, so frames can be read without copying
+/// the file through a buffer. This is faster than [`SliceParser`],
+/// especially for large files or when only some frames are needed.
+///
+/// The values of a frame can't be viewed as `&[f32]` in place, as the
+/// 4-byte time record and the record markers leave them 2 bytes out of
+/// alignment in files written by FDS. [`MmapSlice::value_bytes`] gives the
+/// bytes without copying, and [`MmapSlice::copy_values`] decodes them into a
+/// reusable buffer.
+///
+/// The file must not be truncated or modified while it is mapped. FDS only
+/// appends to slice files, so files from a run that is still going can be
+/// mapped, but frames written after the file was opened are not seen.
+#[derive(Debug)]
+pub struct MmapSlice {
+    mmap: Mmap,
+    pub header: SliceHeader,
+    format: RecordFormat,
+    header_length: u64,
+    frame_length: u64,
+}
+
+impl MmapSlice {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ParseSliceError> {
+        let file = std::fs::File::open(path).map_err(ParseSliceError::IOError)?;
+        // SAFETY: The mapping is read-only, and the file is required not to
+        // be modified while it is mapped.
+        let mmap = unsafe { Mmap::map(&file) }.map_err(ParseSliceError::IOError)?;
+        let parser = SliceParser::new(std::io::Cursor::new(&mmap[..]))?;
+        let header = parser.header.clone();
+        let format = parser.format();
+        let header_length = parser.header_length();
+        let frame_length = parser.frame_length();
+        Ok(Self {
+            mmap,
+            header,
+            format,
+            header_length,
+            frame_length,
+        })
+    }
+
+    pub fn format(&self) -> RecordFormat {
+        self.format
+    }
+
+    /// The number of complete frames in the file.
+    pub fn n_frames(&self) -> usize {
+        (self.data_length() / self.frame_length) as usize
+    }
+
+    /// Whether the file ends with an incomplete frame, as happens while FDS
+    /// is writing it.
+    pub fn has_partial_frame(&self) -> bool {
+        self.data_length() > self.n_frames() as u64 * self.frame_length
+    }
+
+    fn data_length(&self) -> u64 {
+        (self.mmap.len() as u64).saturating_sub(self.header_length)
+    }
+
+    pub fn time(&self, frame: usize) -> Result<f32, ParseSliceError> {
+        Ok(self.frame_records(frame)?.0)
+    }
+
+    /// The raw bytes of the values of a frame, in the byte order of the file.
+    /// These are a view into the mapped file, so nothing is copied.
+    pub fn value_bytes(&self, frame: usize) -> Result<&[u8], ParseSliceError> {
+        Ok(self.frame_records(frame)?.1)
+    }
+
+    /// Copy the values of a frame into `values`, replacing its contents. This
+    /// allows a buffer to be reused when reading many frames.
+    pub fn copy_values(&self, frame: usize, values: &mut Vec<f32>) -> Result<(), ParseSliceError> {
+        let bytes = self.value_bytes(frame)?;
+        values.clear();
+        self.decode_into(bytes, values);
+        Ok(())
+    }
+
+    pub fn frame(&self, frame: usize) -> Result<Frame, ParseSliceError> {
+        let (time, bytes) = self.frame_records(frame)?;
+        Ok(Frame {
+            time,
+            values: self.decode(bytes),
+        })
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Vec<f32> {
+        let mut values = Vec::with_capacity(bytes.len() / 4);
+        self.decode_into(bytes, &mut values);
+        values
+    }
+
+    fn decode_into(&self, bytes: &[u8], values: &mut Vec<f32>) {
+        let from_bytes = match self.format.endianness() {
+            Endianness::Little => f32::from_le_bytes,
+            Endianness::Big => f32::from_be_bytes,
+        };
+        values.extend(
+            bytes
+                .chunks_exact(4)
+                .map(|b| from_bytes([b[0], b[1], b[2], b[3]])),
+        );
+    }
+
+    /// The time and the bytes of the values of a frame, checking the record
+    /// markers.
+    fn frame_records(&self, frame: usize) -> Result<(f32, &[u8]), ParseSliceError> {
+        if frame >= self.n_frames() {
+            return Err(ParseSliceError::IncompleteFrame);
+        }
+        let offset = self.header_length + self.frame_length * frame as u64;
+        let time = self.decode(self.record(offset, 4)?)[0];
+        let values = self.record(
+            offset + self.format.record_length(4),
+            self.header.dimensions.n_values() * 4,
+        )?;
+        Ok((time, values))
+    }
+
+    /// The data of the record at `offset`, which must be `length` bytes long
+    /// and lie within the file.
+    fn record(&self, offset: u64, length: u64) -> Result<&[u8], ParseSliceError> {
+        let width = self.format.marker_width().bytes() as u64;
+        for at in [offset, offset + width + length].iter().copied() {
+            let found = self
+                .format
+                .read_marker(&mut &self.mmap[at as usize..])
+                .map_err(ParseSliceError::IOError)?;
+            if found != length {
+                return Err(ParseSliceError::RecLengthError {
+                    offset: at,
+                    expected: length,
+                    found,
+                });
+            }
+        }
+        let start = (offset + width) as usize;
+        Ok(&self.mmap[start..start + length as usize])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_slice_file;
+
+    #[test]
+    fn mmap_slice_frames() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/room_fire_01.sf");
+        let slice = MmapSlice::open(path).unwrap();
+        let expected =
+            parse_slice_file(&mut std::io::Cursor::new(include_bytes!("room_fire_01.sf"))).unwrap();
+        assert_eq!(slice.header, expected.header);
+        assert_eq!(slice.n_frames(), expected.frames.len());
+        let mut values = Vec::new();
+        for (n, frame) in expected.frames.iter().enumerate() {
+            assert_eq!(slice.time(n).unwrap(), frame.time);
+            // The values start 2 bytes past a 4-byte boundary, so can't be
+            // viewed in place as f32.
+            let bytes = slice.value_bytes(n).unwrap();
+            let offset = bytes.as_ptr() as usize - slice.mmap.as_ptr() as usize;
+            assert_eq!(offset % 4, 2);
+            slice.copy_values(n, &mut values).unwrap();
+            assert_eq!(values, frame.values);
+        }
+        assert_eq!(slice.frame(10).unwrap(), expected.frames[10]);
+        assert!(matches!(
+            slice.frame(expected.frames.len()),
+            Err(ParseSliceError::IncompleteFrame)
+        ));
+    }
+}
@@ -1,3 +1,6 @@
+use crate::fortran_record::OffsetReader;
+use crate::{GridRegion, RecordError, RecordFormat};
+use std::io::BufRead;
 use std::io::BufReader;
 use std::io::Read;
 use std::io::Seek;
@@ -33,22 +36,132 @@ pub struct Dimensions {
     pub k_max: u32,
 }
 
+impl Dimensions {
+    /// The number of values along each axis.
+    pub fn shape(&self) -> (u32, u32, u32) {
+        (
+            self.i_max - self.i_min + 1,
+            self.j_max - self.j_min + 1,
+            self.k_max - self.k_min + 1,
+        )
+    }
+    /// The number of values in each frame.
+    pub fn n_values(&self) -> u64 {
+        let (i_dim, j_dim, k_dim) = self.shape();
+        i_dim as u64 * j_dim as u64 * k_dim as u64
+    }
+    pub(crate) fn is_valid(&self) -> bool {
+        self.i_max >= self.i_min && self.j_max >= self.j_min && self.k_max >= self.k_min
+    }
+}
+
+impl From<Dimensions> for GridRegion {
+    fn from(dimensions: Dimensions) -> Self {
+        GridRegion::new(
+            dimensions.i_min as i64,
+            dimensions.i_max as i64,
+            dimensions.j_min as i64,
+            dimensions.j_max as i64,
+            dimensions.k_min as i64,
+            dimensions.k_max as i64,
+        )
+    }
+}
+
+/// The fields of a slice file header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SliceHeaderField {
+    Quantity,
+    ShortName,
+    Units,
+    Dimensions,
+}
+
+impl std::fmt::Display for SliceHeaderField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Quantity => write!(f, "quantity"),
+            Self::ShortName => write!(f, "short name"),
+            Self::Units => write!(f, "units"),
+            Self::Dimensions => write!(f, "dimensions"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ParseSliceError {
     IOError(std::io::Error),
-    RecLengthError,
+    /// A record does not have the expected length, or the length markers at
+    /// its start and end differ. `offset` is the position of the start of the
+    /// record in bytes.
+    RecLengthError {
+        offset: u64,
+        expected: u64,
+        found: u64,
+    },
+    /// A text record is not valid UTF-8.
+    InvalidUtf8 {
+        offset: u64,
+        source: std::string::FromUtf8Error,
+    },
+    /// The maximum of a dimension is less than its minimum.
+    InvalidDimensions(Dimensions),
+    /// A field of the header could not be read.
+    HeaderError {
+        field: SliceHeaderField,
+        source: Box<ParseSliceError>,
+    },
+    /// The data ends part way through a frame. This is expected while FDS is
+    /// still writing the file.
+    IncompleteFrame,
 }
 
 impl std::fmt::Display for ParseSliceError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "A parsing error occurred.")
+        match self {
+            Self::IOError(err) => write!(f, "could not read slice file: {}", err),
+            Self::RecLengthError {
+                offset,
+                expected,
+                found,
+            } => write!(
+                f,
+                "record at byte {} has length {} but {} was expected",
+                offset, found, expected
+            ),
+            Self::InvalidUtf8 { offset, .. } => {
+                write!(f, "text record at byte {} is not valid UTF-8", offset)
+            }
+            Self::InvalidDimensions(dimensions) => write!(
+                f,
+                "invalid slice dimensions i: {}-{} j: {}-{} k: {}-{}",
+                dimensions.i_min,
+                dimensions.i_max,
+                dimensions.j_min,
+                dimensions.j_max,
+                dimensions.k_min,
+                dimensions.k_max
+            ),
+            Self::HeaderError { field, source } => {
+                write!(
+                    f,
+                    "could not read the {} of the slice header: {}",
+                    field, source
+                )
+            }
+            Self::IncompleteFrame => write!(f, "slice file ends part way through a frame"),
+        }
     }
 }
 impl std::error::Error for ParseSliceError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        match *self {
-            Self::IOError(ref e) => e.source(),
-            Self::RecLengthError => None,
+        match self {
+            Self::IOError(e) => Some(e),
+            Self::InvalidUtf8 { source, .. } => Some(source),
+            Self::HeaderError { source, .. } => Some(source.as_ref()),
+            Self::RecLengthError { .. } | Self::InvalidDimensions(_) | Self::IncompleteFrame => {
+                None
+            }
         }
     }
 }
@@ -59,14 +172,28 @@ pub struct SliceParser<R> {
     pub header: SliceHeader,
     current_frame: usize,
     header_length: u64,
+    format: RecordFormat,
+    /// The time of each frame, read as required by [`SliceParser::times`].
+    times: Vec<f32>,
 }
 
 impl<R: Read> SliceParser<R> {
     pub fn parse_frame(&mut self) -> Result<Frame, ParseSliceError> {
-        let i_dim = self.header.dimensions.i_max - self.header.dimensions.i_min + 1;
-        let j_dim = self.header.dimensions.j_max - self.header.dimensions.j_min + 1;
-        let k_dim = self.header.dimensions.k_max - self.header.dimensions.k_min + 1;
-        let frame = parse_data_set(i_dim, j_dim, k_dim, &mut self.reader);
+        let (i_dim, j_dim, k_dim) = self.header.dimensions.shape();
+        let offset = self.header_length + self.frame_length() * self.current_frame as u64;
+        let mut reader = OffsetReader {
+            inner: &mut self.reader,
+            offset,
+            format: self.format,
+        };
+        let frame = match parse_data_set_at(i_dim, j_dim, k_dim, &mut reader) {
+            Err(ParseSliceError::IOError(err))
+                if err.kind() == std::io::ErrorKind::UnexpectedEof =>
+            {
+                Err(ParseSliceError::IncompleteFrame)
+            }
+            frame => frame,
+        };
         if frame.is_ok() {
             // TODO: needs better error handling
             self.current_frame += 1;
@@ -76,225 +203,316 @@ impl<R: Read> SliceParser<R> {
     pub fn header_length(&self) -> u64 {
         self.header_length
     }
+    /// The record format of the file, which is detected from the header.
+    pub fn format(&self) -> RecordFormat {
+        self.format
+    }
     pub fn frame_length(&self) -> u64 {
-        // Time record 4+4+4
-        let time_length = 4 + 4 + 4;
-        let i_dim = self.header.dimensions.i_max - self.header.dimensions.i_min + 1;
-        let j_dim = self.header.dimensions.j_max - self.header.dimensions.j_min + 1;
-        let k_dim = self.header.dimensions.k_max - self.header.dimensions.k_min + 1;
-        let n_values = (i_dim * j_dim * k_dim) as usize;
-        let data_length = 4 + n_values * 4 + 4;
-        (time_length + data_length) as u64
+        let time_length = self.format.record_length(4);
+        let data_length = self
+            .format
+            .record_length(self.header.dimensions.n_values() * 4);
+        time_length + data_length
     }
 }
 impl<R: Read + std::io::Seek> SliceParser<R> {
     pub fn new(input: R) -> Result<Self, ParseSliceError> {
         let mut reader = BufReader::new(input);
-        let header = parse_slice_header(&mut reader)?;
+        let offset = reader.stream_position().map_err(ParseSliceError::IOError)?;
+        let format = RecordFormat::detect(&mut reader).map_err(ParseSliceError::IOError)?;
+        let header = parse_slice_header(&mut OffsetReader {
+            inner: &mut reader,
+            offset,
+            format,
+        })?;
         let header_length = reader.stream_position().map_err(ParseSliceError::IOError)?;
         Ok(SliceParser {
             reader,
             header,
             current_frame: 0,
             header_length,
+            format,
+            times: Vec::new(),
         })
     }
 }
 impl<R: Read + std::io::Seek> SliceParser<R> {
     pub fn seek_next_frame(&mut self) -> std::io::Result<()> {
-        self.reader.seek_relative(self.frame_length() as i64)
+        self.reader.seek_relative(self.frame_length() as i64)?;
+        self.current_frame += 1;
+        Ok(())
     }
     pub fn seek_frame(&mut self, frame: usize) -> std::io::Result<u64> {
-        self.reader.seek(SeekFrom::Start(
+        let position = self.reader.seek(SeekFrom::Start(
             self.header_length() + self.frame_length() * (frame as u64),
-        ))
+        ))?;
+        self.current_frame = frame;
+        Ok(position)
     }
     pub fn get_frame(&mut self, frame: usize) -> Result<Frame, ParseSliceError> {
         self.seek_frame(frame).map_err(ParseSliceError::IOError)?;
-        self.current_frame = frame;
         self.parse_frame()
     }
+    /// The number of complete frames, from the length of the data.
+    pub fn n_frames(&mut self) -> std::io::Result<usize> {
+        Ok((self.data_length()? / self.frame_length()) as usize)
+    }
+    /// Whether the data ends with an incomplete frame, e.g. because FDS is
+    /// still writing it.
+    pub fn has_partial_frame(&mut self) -> std::io::Result<bool> {
+        Ok(self.data_length()? % self.frame_length() > 0)
+    }
+    /// The time of each complete frame. Only the time records are read,
+    /// seeking past the values of each frame. The times are kept, and when
+    /// called again only the times of frames written since are read. The
+    /// position is left unchanged.
+    pub fn times(&mut self) -> Result<&[f32], ParseSliceError> {
+        let n_frames = self.n_frames().map_err(ParseSliceError::IOError)?;
+        if self.times.len() < n_frames {
+            let position = self
+                .reader
+                .stream_position()
+                .map_err(ParseSliceError::IOError)?;
+            let result = self.read_times(n_frames);
+            self.reader
+                .seek(SeekFrom::Start(position))
+                .map_err(ParseSliceError::IOError)?;
+            result?;
+        }
+        Ok(&self.times)
+    }
+    fn read_times(&mut self, n_frames: usize) -> Result<(), ParseSliceError> {
+        let time_length = self.format.record_length(4);
+        let skip = (self.frame_length() - time_length) as i64;
+        let mut offset = self.header_length + self.frame_length() * self.times.len() as u64;
+        self.reader
+            .seek(SeekFrom::Start(offset))
+            .map_err(ParseSliceError::IOError)?;
+        while self.times.len() < n_frames {
+            let mut reader = OffsetReader {
+                inner: &mut self.reader,
+                offset,
+                format: self.format,
+            };
+            let record = parse_record(&mut reader, Some(4))?;
+            match self.format.decode_f32s(&record).as_deref() {
+                Ok([time]) => self.times.push(*time),
+                _ => unreachable!("time record length has been checked"),
+            }
+            self.reader
+                .seek_relative(skip)
+                .map_err(ParseSliceError::IOError)?;
+            offset += self.frame_length();
+        }
+        Ok(())
+    }
+    /// The frame with the time closest to `t`. Returns None if there are no
+    /// frames.
+    pub fn frame_at_time(&mut self, t: f32) -> Result<Option<Frame>, ParseSliceError> {
+        let times = self.times()?;
+        let after = times.partition_point(|time| *time < t);
+        let frame = match (after.checked_sub(1), times.get(after)) {
+            (Some(before), Some(time)) if t - times[before] > time - t => after,
+            (Some(before), _) => before,
+            (None, Some(_)) => after,
+            (None, None) => return Ok(None),
+        };
+        self.get_frame(frame).map(Some)
+    }
+    /// The frames with times from `t0` to `t1` inclusive.
+    pub fn frames_between(&mut self, t0: f32, t1: f32) -> Result<Vec<Frame>, ParseSliceError> {
+        let times = self.times()?;
+        let start = times.partition_point(|time| *time < t0);
+        let end = times.partition_point(|time| *time <= t1);
+        if start >= end {
+            return Ok(Vec::new());
+        }
+        self.seek_frame(start).map_err(ParseSliceError::IOError)?;
+        (start..end).map(|_| self.parse_frame()).collect()
+    }
+    /// The values at time `t`, interpolated linearly between the frames
+    /// either side. Returns None if `t` is outside the times of the frames.
+    pub fn value_at_time(&mut self, t: f32) -> Result<Option<Frame>, ParseSliceError> {
+        let times = self.times()?;
+        let after = times.partition_point(|time| *time < t);
+        let (t1, t0) = match (times.get(after), after.checked_sub(1)) {
+            (Some(t1), _) if *t1 == t => return self.get_frame(after).map(Some),
+            (Some(t1), Some(before)) => (*t1, times[before]),
+            _ => return Ok(None),
+        };
+        let before = self.get_frame(after - 1)?;
+        let next = self.parse_frame()?;
+        let w = (t - t0) / (t1 - t0);
+        let values = before
+            .values
+            .iter()
+            .zip(&next.values)
+            .map(|(v0, v1)| v0 + (v1 - v0) * w)
+            .collect();
+        Ok(Some(Frame { time: t, values }))
+    }
+    /// The length of the data following the header, leaving the position
+    /// unchanged.
+    fn data_length(&mut self) -> std::io::Result<u64> {
+        let position = self.reader.stream_position()?;
+        let end = self.reader.seek(SeekFrom::End(0))?;
+        self.reader.seek(SeekFrom::Start(position))?;
+        Ok(end.saturating_sub(self.header_length))
+    }
 }
-impl<R: Read> Iterator for SliceParser<R> {
+/// Iterates over the frames from the current position. Iteration ends at the
+/// end of the data or at an incomplete frame, in which case the position is
+/// returned to the start of that frame. Calling `next` again will then read
+/// any frames written since, so a file that is still being written can be
+/// read incrementally.
+impl<R: Read + Seek> Iterator for SliceParser<R> {
     type Item = Result<Frame, ParseSliceError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let frame = self.parse_frame();
-        Some(frame)
+        match self.reader.fill_buf() {
+            Ok([]) => return None,
+            Ok(_) => (),
+            Err(err) => return Some(Err(ParseSliceError::IOError(err))),
+        }
+        match self.parse_frame() {
+            Err(ParseSliceError::IncompleteFrame) => match self.seek_frame(self.current_frame) {
+                Ok(_) => None,
+                Err(err) => Some(Err(ParseSliceError::IOError(err))),
+            },
+            frame => Some(frame),
+        }
     }
 }
 
+/// Parse a slice file. An incomplete frame at the end of the file is ignored,
+/// but any other error in the frames is returned.
 pub fn parse_slice_file<R: Read + Seek>(i: &mut R) -> Result<SliceFile, ParseSliceError> {
     let parser = SliceParser::new(i)?;
-    eprintln!("header: {:?}", parser.header);
     let header = parser.header.clone();
-    let mut frames = Vec::new();
-    for (i, frame) in parser.enumerate() {
-        eprintln!("frame[{}]: {:?}", i, frame.as_ref().map(|x| x.time));
-        if let Ok(frame) = frame {
-            frames.push(frame);
-        } else {
-            break;
-        }
-    }
+    let frames = parser.collect::<Result<Vec<_>, _>>()?;
     Ok(SliceFile { header, frames })
 }
 
+/// Parse a frame, in the default [`RecordFormat`]. Offsets in errors are
+/// relative to the start of the frame.
 pub fn parse_data_set<R: Read>(
     i_dim: u32,
     j_dim: u32,
     k_dim: u32,
-    mut i: R,
+    i: R,
 ) -> Result<Frame, ParseSliceError> {
-    let rec_length = {
-        let mut buf = [0u8; 4];
-        i.read_exact(&mut buf).map_err(ParseSliceError::IOError)?;
-        u32::from_le_bytes(buf)
-    };
-    let time = {
-        let mut buf = [0u8; 4];
-        i.read_exact(&mut buf).map_err(ParseSliceError::IOError)?;
-        f32::from_le_bytes(buf)
-    };
-    let check_length = {
-        let mut buf = [0u8; 4];
-        i.read_exact(&mut buf).map_err(ParseSliceError::IOError)?;
-        u32::from_le_bytes(buf)
+    parse_data_set_at(i_dim, j_dim, k_dim, &mut OffsetReader::new(i))
+}
+
+fn parse_data_set_at<R: Read>(
+    i_dim: u32,
+    j_dim: u32,
+    k_dim: u32,
+    i: &mut OffsetReader<R>,
+) -> Result<Frame, ParseSliceError> {
+    let time_record = parse_record(i, Some(4))?;
+    let time = match i.format.decode_f32s(&time_record).as_deref() {
+        Ok([time]) => *time,
+        _ => unreachable!("time record length has been checked"),
     };
-    if check_length != rec_length {
-        return Err(ParseSliceError::RecLengthError);
-    }
-    let values = parse_slice_data(i_dim, j_dim, k_dim, i)?;
+    let values = parse_slice_data_at(i_dim, j_dim, k_dim, i)?;
     Ok(Frame { time, values })
 }
 
+/// Parse the values of a frame, in the default [`RecordFormat`]. Offsets in
+/// errors are relative to the start of the record.
 pub fn parse_slice_data<R: Read>(
     i_dim: u32,
     j_dim: u32,
     k_dim: u32,
-    mut i: R,
+    i: R,
 ) -> Result<Vec<f32>, ParseSliceError> {
-    let rec_length = {
-        let mut buf = [0u8; 4];
-        i.read_exact(&mut buf).map_err(ParseSliceError::IOError)?;
-        u32::from_le_bytes(buf)
-    };
-    let n_values = (i_dim * j_dim * k_dim) as usize;
-    let mut values = Vec::with_capacity(n_values);
-    let mut buf = [0u8; 4];
-    for _ in 0..n_values {
-        let value = {
-            i.read_exact(&mut buf).map_err(ParseSliceError::IOError)?;
-            f32::from_le_bytes(buf)
-        };
-        values.push(value);
-    }
-    let check_length = {
-        let mut buf = [0u8; 4];
-        i.read_exact(&mut buf).map_err(ParseSliceError::IOError)?;
-        u32::from_le_bytes(buf)
-    };
-    if check_length != rec_length {
-        return Err(ParseSliceError::RecLengthError);
-    }
-    Ok(values)
+    parse_slice_data_at(i_dim, j_dim, k_dim, &mut OffsetReader::new(i))
+}
+
+fn parse_slice_data_at<R: Read>(
+    i_dim: u32,
+    j_dim: u32,
+    k_dim: u32,
+    i: &mut OffsetReader<R>,
+) -> Result<Vec<f32>, ParseSliceError> {
+    let n_values = i_dim as u64 * j_dim as u64 * k_dim as u64;
+    let record = parse_record(i, Some(n_values * 4))?;
+    // The length has been checked so the record holds whole values.
+    Ok(i.format.decode_f32s(&record).unwrap_or_default())
 }
 
-fn parse_slice_header<R: Read>(i: &mut R) -> Result<SliceHeader, ParseSliceError> {
-    let quantity = parse_record(i)?;
-    let short_name = parse_record(i)?;
-    let units = parse_record(i)?;
-    let dimensions = parse_dimensions(i)?;
+fn parse_slice_header<R: Read>(i: &mut OffsetReader<R>) -> Result<SliceHeader, ParseSliceError> {
+    let quantity = parse_header_string(i, SliceHeaderField::Quantity)?;
+    let short_name = parse_header_string(i, SliceHeaderField::ShortName)?;
+    let units = parse_header_string(i, SliceHeaderField::Units)?;
+    let dimensions = parse_dimensions(i).map_err(|err| ParseSliceError::HeaderError {
+        field: SliceHeaderField::Dimensions,
+        source: Box::new(err),
+    })?;
     Ok(SliceHeader {
-        quantity: String::from_utf8(quantity).unwrap(),
-        short_name: String::from_utf8(short_name).unwrap(),
-        units: String::from_utf8(units).unwrap(),
+        quantity,
+        short_name,
+        units,
         dimensions,
     })
 }
 
-fn parse_dimensions<R: Read>(mut i: R) -> Result<Dimensions, ParseSliceError> {
-    let rec_length = {
-        let mut buf = [0u8; 4];
-        i.read_exact(&mut buf).map_err(ParseSliceError::IOError)?;
-        u32::from_le_bytes(buf)
+fn parse_header_string<R: Read>(
+    i: &mut OffsetReader<R>,
+    field: SliceHeaderField,
+) -> Result<String, ParseSliceError> {
+    let offset = i.offset;
+    parse_record(i, None)
+        .and_then(|record| {
+            String::from_utf8(record)
+                .map_err(|source| ParseSliceError::InvalidUtf8 { offset, source })
+        })
+        .map_err(|err| ParseSliceError::HeaderError {
+            field,
+            source: Box::new(err),
+        })
+}
+
+fn parse_dimensions<R: Read>(i: &mut OffsetReader<R>) -> Result<Dimensions, ParseSliceError> {
+    let record = parse_record(i, Some(24))?;
+    let values = i.format.decode_u32s(&record).unwrap_or_default();
+    let mut values = values.into_iter();
+    let mut next = || values.next().unwrap_or_default();
+    let dimensions = Dimensions {
+        i_min: next(),
+        i_max: next(),
+        j_min: next(),
+        j_max: next(),
+        k_min: next(),
+        k_max: next(),
     };
-    if rec_length != 24 {
-        return Err(ParseSliceError::RecLengthError);
+    if !dimensions.is_valid() {
+        return Err(ParseSliceError::InvalidDimensions(dimensions));
     }
-    let i1 = {
-        let mut buf = [0u8; 4];
-        i.read_exact(&mut buf).map_err(ParseSliceError::IOError)?;
-        u32::from_le_bytes(buf)
-    };
-    let i2 = {
-        let mut buf = [0u8; 4];
-        i.read_exact(&mut buf).map_err(ParseSliceError::IOError)?;
-        u32::from_le_bytes(buf)
-    };
-    let j1 = {
-        let mut buf = [0u8; 4];
-        i.read_exact(&mut buf).map_err(ParseSliceError::IOError)?;
-        u32::from_le_bytes(buf)
-    };
-    let j2 = {
-        let mut buf = [0u8; 4];
-        i.read_exact(&mut buf).map_err(ParseSliceError::IOError)?;
-        u32::from_le_bytes(buf)
-    };
-    let k1 = {
-        let mut buf = [0u8; 4];
-        i.read_exact(&mut buf).map_err(ParseSliceError::IOError)?;
-        u32::from_le_bytes(buf)
-    };
-    let k2 = {
-        let mut buf = [0u8; 4];
-        i.read_exact(&mut buf).map_err(ParseSliceError::IOError)?;
-        u32::from_le_bytes(buf)
-    };
-    let check_length = {
-        let mut buf = [0u8; 4];
-        i.read_exact(&mut buf).map_err(ParseSliceError::IOError)?;
-        u32::from_le_bytes(buf)
-    };
-    if check_length != rec_length {
-        return Err(ParseSliceError::RecLengthError);
-    }
-    Ok(Dimensions {
-        i_min: i1,
-        i_max: i2,
-        j_min: j1,
-        j_max: j2,
-        k_min: k1,
-        k_max: k2,
-    })
+    Ok(dimensions)
 }
 
 /// Parse the data from a record, ensuring the record length tags at the start
-/// and finish match.
-fn parse_record<R: Read>(i: &mut R) -> Result<Vec<u8>, ParseSliceError> {
-    // Take the length of the record, which is the first 4 bytes of the record
-    // as a 32-bit as an integer. The length is in bytes.
-    let rec_length = {
-        let mut buf = [0u8; 4];
-        i.read_exact(&mut buf).map_err(ParseSliceError::IOError)?;
-        u32::from_le_bytes(buf)
-    };
-    // Take the number of bytes specified by rec_length.
-    let rec_bytes = {
-        let mut buf = vec![0u8; rec_length as usize];
-        i.read_exact(&mut buf).map_err(ParseSliceError::IOError)?;
-        buf
+/// and finish match, and that the length is as expected if given.
+fn parse_record<R: Read>(
+    i: &mut OffsetReader<R>,
+    expected: Option<u64>,
+) -> Result<Vec<u8>, ParseSliceError> {
+    let offset = i.offset;
+    let format = i.format;
+    let record = match expected {
+        Some(expected) => format.read_record_expecting(i, expected),
+        None => format.read_record(i),
     };
-    let check_length = {
-        let mut buf = [0u8; 4];
-        i.read_exact(&mut buf).map_err(ParseSliceError::IOError)?;
-        u32::from_le_bytes(buf)
-    };
-    if check_length != rec_length {
-        panic!("bad rec_length start: {} end: {}", rec_length, check_length);
-    }
-    Ok(rec_bytes)
+    record.map_err(|err| match err {
+        RecordError::IOError(err) => ParseSliceError::IOError(err),
+        RecordError::RecLengthError { expected, found } => ParseSliceError::RecLengthError {
+            offset,
+            expected,
+            found,
+        },
+    })
 }
 
 #[cfg(test)]
@@ -336,6 +554,153 @@ mod tests {
         let result = parse_slice_file(&mut std::io::Cursor::new(include_bytes!(
             "room_fire_01_bad01.sf"
         )));
-        assert!(result.is_err())
+        match result {
+            Err(ParseSliceError::HeaderError { field, source }) => {
+                assert_eq!(field, SliceHeaderField::Dimensions);
+                assert!(matches!(
+                    *source,
+                    ParseSliceError::RecLengthError {
+                        offset: 114,
+                        expected: 24,
+                        ..
+                    }
+                ));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_slice_invalid_utf8() {
+        let mut data = include_bytes!("room_fire_01.sf").to_vec();
+        // The first character of the units, after the quantity and short name
+        data[2 * 38 + 4] = 0xff;
+        let err = parse_slice_file(&mut std::io::Cursor::new(&data)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "could not read the units of the slice header: text record at byte 76 is not valid UTF-8"
+        );
+    }
+
+    #[test]
+    fn parse_slice_big_endian() {
+        // Rewrite the file as big-endian with 8-byte record markers. The
+        // first three records are text and the rest hold 4-byte values.
+        let data = include_bytes!("room_fire_01.sf");
+        let (little, big) = (
+            RecordFormat::default(),
+            RecordFormat::new(crate::Endianness::Big, crate::MarkerWidth::Eight),
+        );
+        let mut input = std::io::Cursor::new(&data[..]);
+        let mut converted = Vec::new();
+        let mut n = 0;
+        while (input.position() as usize) < data.len() {
+            let mut record = little.read_record(&mut input).unwrap();
+            if n >= 3 {
+                record.chunks_exact_mut(4).for_each(|b| b.reverse());
+            }
+            big.write_record(&mut converted, &record).unwrap();
+            n += 1;
+        }
+        let mut parser = SliceParser::new(std::io::Cursor::new(&converted)).unwrap();
+        assert_eq!(parser.format(), big);
+        assert_eq!(parser.n_frames().unwrap(), 945);
+        let expected =
+            parse_slice_file(&mut std::io::Cursor::new(include_bytes!("room_fire_01.sf"))).unwrap();
+        assert_eq!(parser.header, expected.header);
+        assert_eq!(parser.get_frame(944).unwrap(), expected.frames[944]);
+    }
+
+    #[test]
+    fn parse_slice_in_progress() {
+        let data = include_bytes!("room_fire_01.sf");
+        let path =
+            std::env::temp_dir().join(format!("red-smv-in-progress-{}.sf", std::process::id()));
+        let parser = SliceParser::new(std::io::Cursor::new(&data[..])).unwrap();
+        let split = parser.header_length() as usize + parser.frame_length() as usize * 10 + 100;
+        std::fs::write(&path, &data[..split]).unwrap();
+
+        // Only the complete frames are read
+        let mut parser = SliceParser::new(std::fs::File::open(&path).unwrap()).unwrap();
+        assert_eq!(parser.n_frames().unwrap(), 10);
+        assert!(parser.has_partial_frame().unwrap());
+        assert_eq!(parser.times().unwrap().len(), 10);
+        assert_eq!(parser.by_ref().count(), 10);
+        assert!(parse_slice_file(&mut std::fs::File::open(&path).unwrap()).is_ok());
+
+        // The remaining frames are read once they have been written
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        std::io::Write::write_all(&mut file, &data[split..]).unwrap();
+        assert_eq!(parser.n_frames().unwrap(), 945);
+        assert!(!parser.has_partial_frame().unwrap());
+        assert_eq!(parser.times().unwrap().len(), 945);
+        let frames = parser.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(frames.len(), 935);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn slice_time_index() {
+        let expected =
+            parse_slice_file(&mut std::io::Cursor::new(include_bytes!("room_fire_01.sf"))).unwrap();
+        let mut parser =
+            SliceParser::new(std::io::Cursor::new(include_bytes!("room_fire_01.sf"))).unwrap();
+        let times: Vec<f32> = expected.frames.iter().map(|frame| frame.time).collect();
+        assert_eq!(parser.times().unwrap(), times.as_slice());
+        // The position is unchanged
+        assert_eq!(parser.next().unwrap().unwrap(), expected.frames[0]);
+
+        let (t5, t6) = (times[5], times[6]);
+        let frame = parser.frame_at_time(t5 + (t6 - t5) * 0.4).unwrap();
+        assert_eq!(frame.as_ref(), Some(&expected.frames[5]));
+        let frame = parser.frame_at_time(t5 + (t6 - t5) * 0.6).unwrap();
+        assert_eq!(frame.as_ref(), Some(&expected.frames[6]));
+        let frame = parser.frame_at_time(-10.0).unwrap();
+        assert_eq!(frame.as_ref(), Some(&expected.frames[0]));
+        let frame = parser.frame_at_time(1e9).unwrap();
+        assert_eq!(frame.as_ref(), expected.frames.last());
+
+        let frames = parser.frames_between(t5, times[9]).unwrap();
+        assert_eq!(frames, expected.frames[5..=9].to_vec());
+        assert!(parser.frames_between(t6, t5).unwrap().is_empty());
+
+        let middle = parser.value_at_time((t5 + t6) / 2.0).unwrap().unwrap();
+        for ((v, v5), v6) in middle
+            .values
+            .iter()
+            .zip(&expected.frames[5].values)
+            .zip(&expected.frames[6].values)
+        {
+            assert!((v - (v5 + v6) / 2.0).abs() < 1e-3);
+        }
+        let frame = parser.value_at_time(t6).unwrap();
+        assert_eq!(frame.as_ref(), Some(&expected.frames[6]));
+        assert!(parser.value_at_time(times[0] - 1.0).unwrap().is_none());
+        assert!(parser.value_at_time(times[944] + 1.0).unwrap().is_none());
+    }
+
+    #[test]
+    fn parse_slice_corrupt_frame() {
+        let mut data = include_bytes!("room_fire_01.sf").to_vec();
+        let parser = SliceParser::new(std::io::Cursor::new(&data)).unwrap();
+        let offset = parser.header_length() as usize + parser.frame_length() as usize * 3;
+        data[offset] += 1;
+        let mut parser = SliceParser::new(std::io::Cursor::new(&data)).unwrap();
+        assert_eq!(
+            parser
+                .by_ref()
+                .take(3)
+                .filter(|frame| frame.is_ok())
+                .count(),
+            3
+        );
+        assert!(matches!(
+            parser.next(),
+            Some(Err(ParseSliceError::RecLengthError { .. }))
+        ));
+        assert!(parse_slice_file(&mut std::io::Cursor::new(&data)).is_err());
     }
 }
@@ -0,0 +1,457 @@
+use crate::probe::{value_positions, TOLERANCE};
+use crate::slice_stitch::{component, plane_axes};
+use crate::{Axis, Frame, Rgba, SliceHeader, SmvMesh, Xyz};
+use std::borrow::Borrow;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The colour maps available to render slices.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ColourMap {
+    /// The default colour bar of Smokeview, from blue through green to red.
+    Rainbow,
+    /// The perceptually uniform colour map of matplotlib.
+    Viridis,
+    Greyscale,
+}
+
+impl ColourMap {
+    /// The colour at `f`, from 0 for the bottom of the range to 1 for the
+    /// top. Values outside this are clamped.
+    pub fn colour(&self, f: f64) -> Rgba {
+        let nodes: &[[u8; 3]] = match self {
+            Self::Rainbow => &[
+                [0, 0, 255],
+                [0, 255, 255],
+                [0, 255, 0],
+                [255, 255, 0],
+                [255, 0, 0],
+            ],
+            Self::Viridis => &[
+                [68, 1, 84],
+                [71, 44, 122],
+                [59, 81, 139],
+                [44, 113, 142],
+                [33, 144, 141],
+                [39, 173, 129],
+                [92, 200, 99],
+                [170, 220, 50],
+                [253, 231, 37],
+            ],
+            Self::Greyscale => &[[0, 0, 0], [255, 255, 255]],
+        };
+        let f = if f.is_nan() { 0.0 } else { f.clamp(0.0, 1.0) };
+        let position = f * (nodes.len() - 1) as f64;
+        let n = (position.floor() as usize).min(nodes.len() - 2);
+        let w = position - n as f64;
+        let mix = |c: usize| (nodes[n][c] as f64 * (1.0 - w) + nodes[n + 1][c] as f64 * w).round();
+        Rgba {
+            r: mix(0) as u8,
+            g: mix(1) as u8,
+            b: mix(2) as u8,
+            a: 255,
+        }
+    }
+}
+
+/// The range of values covered by a colour map.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ValueRange {
+    /// The range of the values in each frame.
+    Auto,
+    Fixed(f32, f32),
+}
+
+impl ValueRange {
+    /// A fixed range covering the values of every frame, so that the colours
+    /// of a sequence of frames can be compared. Returns [`ValueRange::Auto`]
+    /// if there are no finite values.
+    pub fn covering<'a, I: IntoIterator<Item = &'a Frame>>(frames: I) -> Self {
+        frames
+            .into_iter()
+            .filter_map(|frame| finite_range(&frame.values))
+            .fold(None, |range: Option<(f32, f32)>, (min, max)| match range {
+                Some((a, b)) => Some((a.min(min), b.max(max))),
+                None => Some((min, max)),
+            })
+            .map_or(Self::Auto, |(min, max)| Self::Fixed(min, max))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderOptions {
+    pub colour_map: ColourMap,
+    pub range: ValueRange,
+    /// The size of the image in pixels per metre.
+    pub scale: f64,
+    /// Whether to draw a colour bar to the right of the slice, with the top
+    /// of the range at the top.
+    pub colour_bar: bool,
+    /// The colour of obstructions which the slice passes through.
+    pub obst_colour: Rgba,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            colour_map: ColourMap::Rainbow,
+            range: ValueRange::Auto,
+            scale: 100.0,
+            colour_bar: true,
+            obst_colour: Rgba {
+                r: 128,
+                g: 128,
+                b: 128,
+                a: 255,
+            },
+        }
+    }
+}
+
+/// An RGBA image, stored row by row from the top left.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Rgba>,
+}
+
+impl RgbaImage {
+    pub fn get(&self, x: u32, y: u32) -> Option<Rgba> {
+        if x >= self.width {
+            return None;
+        }
+        self.pixels
+            .get(y as usize * self.width as usize + x as usize)
+            .copied()
+    }
+
+    pub fn write_png<W: Write>(&self, w: W) -> std::io::Result<()> {
+        let mut encoder = png::Encoder::new(w, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        let data: Vec<u8> = self
+            .pixels
+            .iter()
+            .flat_map(|p| [p.r, p.g, p.b, p.a])
+            .collect();
+        writer.write_image_data(&data)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+/// A rendered frame, with the range of values covered by the colour map,
+/// which is needed to label the colour bar.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderedFrame {
+    pub image: RgbaImage,
+    pub time: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+/// The width in pixels of the gap before the colour bar, and of the bar.
+const COLOUR_BAR_GAP: u32 = 4;
+const COLOUR_BAR_WIDTH: u32 = 16;
+
+/// Renders the frames of a plane slice as images, looking along the axis
+/// normal to the slice. The horizontal and vertical axes of the image are the
+/// other two axes in the order x, y, z, so a slice normal to y has x to the
+/// right and z up. Each pixel takes the nearest value. Pixels within an
+/// obstruction are drawn in a fixed colour and pixels with a NaN value are
+/// transparent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SliceRenderer {
+    pub quantity: String,
+    pub units: String,
+    /// The axis normal to the slice.
+    pub axis: Axis,
+    /// The positions of the values along the horizontal and vertical axes of
+    /// the image.
+    u: Vec<f64>,
+    v: Vec<f64>,
+    /// The extent of the slice along each axis of the image.
+    u_range: (f64, f64),
+    v_range: (f64, f64),
+    /// The extent of each obstruction the slice passes through, as
+    /// `[u1, u2, v1, v2]`.
+    obsts: Vec<[f64; 4]>,
+}
+
+impl SliceRenderer {
+    /// Create a renderer for a slice of the given mesh. Returns None if the
+    /// slice is not a plane or does not lie on the grid of the mesh.
+    pub fn new(mesh: &SmvMesh, header: &SliceHeader, cell_centred: bool) -> Option<Self> {
+        let axis = match header.dimensions.shape() {
+            (1, j, k) if j > 1 && k > 1 => Axis::X,
+            (i, 1, k) if i > 1 && k > 1 => Axis::Y,
+            (i, j, 1) if i > 1 && j > 1 => Axis::Z,
+            _ => return None,
+        };
+        let (u_axis, v_axis) = plane_axes(axis);
+        let ijk = header.dimensions.into();
+        let [xs, ys, zs] = value_positions(mesh, &ijk, cell_centred)?;
+        let along = |axis: Axis| match axis {
+            Axis::X => xs.clone(),
+            Axis::Y => ys.clone(),
+            Axis::Z => zs.clone(),
+        };
+        let position = along(axis)[0];
+        let range = |xb: &crate::Xb, axis: Axis| {
+            let (a, b) = (
+                component(Xyz::new(xb.x1, xb.y1, xb.z1), axis),
+                component(Xyz::new(xb.x2, xb.y2, xb.z2), axis),
+            );
+            (a.min(b), a.max(b))
+        };
+        let extent = mesh.xb_from_grid(ijk)?;
+        let obsts = mesh
+            .obsts
+            .iter()
+            .map(|obst| mesh.xb_from_grid(obst.ijk).unwrap_or(obst.xb_exact))
+            .filter(|xb| {
+                let (a, b) = range(xb, axis);
+                position > a + TOLERANCE && position < b - TOLERANCE
+            })
+            .map(|xb| {
+                let ((u1, u2), (v1, v2)) = (range(&xb, u_axis), range(&xb, v_axis));
+                [u1, u2, v1, v2]
+            })
+            .collect();
+        Some(Self {
+            quantity: header.quantity.trim().to_string(),
+            units: header.units.trim().to_string(),
+            axis,
+            u: along(u_axis),
+            v: along(v_axis),
+            u_range: range(&extent, u_axis),
+            v_range: range(&extent, v_axis),
+            obsts,
+        })
+    }
+
+    /// The size in pixels of the slice, excluding the colour bar.
+    pub fn size(&self, scale: f64) -> (u32, u32) {
+        let pixels = |(a, b): (f64, f64)| (((b - a) * scale).round() as u32).max(1);
+        (pixels(self.u_range), pixels(self.v_range))
+    }
+
+    /// Render a frame. Returns None if the frame does not have one value for
+    /// each point of the slice.
+    pub fn render(&self, frame: &Frame, options: &RenderOptions) -> Option<RenderedFrame> {
+        if frame.values.len() != self.u.len() * self.v.len() {
+            return None;
+        }
+        let (min, max) = match options.range {
+            ValueRange::Fixed(min, max) => (min, max),
+            ValueRange::Auto => finite_range(&frame.values).unwrap_or((0.0, 1.0)),
+        };
+        let scaled = |value: f32| {
+            if max > min {
+                (value - min) as f64 / (max - min) as f64
+            } else {
+                0.5
+            }
+        };
+        let (width, height) = self.size(options.scale);
+        let full_width = if options.colour_bar {
+            width + COLOUR_BAR_GAP + COLOUR_BAR_WIDTH
+        } else {
+            width
+        };
+        let transparent = Rgba {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 0,
+        };
+        let mut pixels = Vec::with_capacity(full_width as usize * height as usize);
+        for y in 0..height {
+            let pv = self.v_range.1
+                - (y as f64 + 0.5) * (self.v_range.1 - self.v_range.0) / height as f64;
+            let iv = nearest(&self.v, pv);
+            for x in 0..width {
+                let pu = self.u_range.0
+                    + (x as f64 + 0.5) * (self.u_range.1 - self.u_range.0) / width as f64;
+                let in_obst = self
+                    .obsts
+                    .iter()
+                    .any(|[u1, u2, v1, v2]| pu >= *u1 && pu <= *u2 && pv >= *v1 && pv <= *v2);
+                let value = frame.values[nearest(&self.u, pu) + self.u.len() * iv];
+                pixels.push(if in_obst {
+                    options.obst_colour
+                } else if value.is_nan() {
+                    transparent
+                } else {
+                    options.colour_map.colour(scaled(value))
+                });
+            }
+            if options.colour_bar {
+                pixels.extend((0..COLOUR_BAR_GAP).map(|_| transparent));
+                let f = 1.0 - (y as f64 + 0.5) / height as f64;
+                let colour = options.colour_map.colour(f);
+                pixels.extend((0..COLOUR_BAR_WIDTH).map(|_| colour));
+            }
+        }
+        Some(RenderedFrame {
+            image: RgbaImage {
+                width: full_width,
+                height,
+                pixels,
+            },
+            time: frame.time,
+            min,
+            max,
+        })
+    }
+
+    /// Render a frame and write it as a PNG. Returns an error of kind
+    /// `InvalidInput` if the frame does not have one value for each point of
+    /// the slice.
+    pub fn write_png<W: Write>(
+        &self,
+        w: W,
+        frame: &Frame,
+        options: &RenderOptions,
+    ) -> std::io::Result<RenderedFrame> {
+        let rendered = self.render(frame, options).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "slice frame has {} values but the slice has {}",
+                    frame.values.len(),
+                    self.u.len() * self.v.len()
+                ),
+            )
+        })?;
+        rendered.image.write_png(w)?;
+        Ok(rendered)
+    }
+
+    /// Write each frame to a PNG named `{stem}_{n}.png` in `dir`, e.g. to
+    /// make an animation. Returns the paths of the files. Use
+    /// [`ValueRange::covering`] so that every frame uses the same colours.
+    pub fn write_png_series<P, I>(
+        &self,
+        dir: P,
+        stem: &str,
+        frames: I,
+        options: &RenderOptions,
+    ) -> std::io::Result<Vec<PathBuf>>
+    where
+        P: AsRef<Path>,
+        I: IntoIterator,
+        I::Item: Borrow<Frame>,
+    {
+        let dir = dir.as_ref();
+        let mut paths = Vec::new();
+        for (n, frame) in frames.into_iter().enumerate() {
+            let path = dir.join(format!("{}_{:04}.png", stem, n));
+            let mut file = std::io::BufWriter::new(std::fs::File::create(&path)?);
+            self.write_png(&mut file, frame.borrow(), options)?;
+            file.flush()?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+/// The smallest and largest finite values, or None if there are none.
+fn finite_range(values: &[f32]) -> Option<(f32, f32)> {
+    values
+        .iter()
+        .filter(|v| v.is_finite())
+        .fold(None, |range, v| match range {
+            Some((min, max)) => Some((v.min(min), v.max(max))),
+            None => Some((*v, *v)),
+        })
+}
+
+/// The index of the position closest to `p`.
+fn nearest(positions: &[f64], p: f64) -> usize {
+    let after = positions.partition_point(|x| *x < p);
+    match (after.checked_sub(1), positions.get(after)) {
+        (Some(before), Some(x)) if x - p < p - positions[before] => after,
+        (Some(before), _) => before,
+        _ => after,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_slice_file, parse_smv_file};
+
+    #[test]
+    fn colour_maps() {
+        let rgb = |c: Rgba| (c.r, c.g, c.b);
+        assert_eq!(rgb(ColourMap::Rainbow.colour(0.0)), (0, 0, 255));
+        assert_eq!(rgb(ColourMap::Rainbow.colour(0.5)), (0, 255, 0));
+        assert_eq!(rgb(ColourMap::Rainbow.colour(2.0)), (255, 0, 0));
+        assert_eq!(rgb(ColourMap::Viridis.colour(1.0)), (253, 231, 37));
+        assert_eq!(rgb(ColourMap::Greyscale.colour(0.5)), (128, 128, 128));
+    }
+
+    #[test]
+    fn render_room_fire() {
+        let smv = parse_smv_file(std::io::Cursor::new(include_str!("room_fire.smv"))).unwrap();
+        let slcf = &smv.slcfs[0];
+        let mesh = smv.slice_mesh(slcf).unwrap();
+        let slice =
+            parse_slice_file(&mut std::io::Cursor::new(include_bytes!("room_fire_01.sf"))).unwrap();
+        let renderer = SliceRenderer::new(mesh, &slice.header, slcf.cell_centred).unwrap();
+        assert_eq!(renderer.axis, Axis::X);
+        // The slice covers y 3.6 to 4.6 and z 0 to 2.4.
+        let options = RenderOptions {
+            scale: 50.0,
+            ..RenderOptions::default()
+        };
+        assert_eq!(renderer.size(options.scale), (50, 120));
+
+        let frame = &slice.frames[100];
+        let rendered = renderer.render(frame, &options).unwrap();
+        let image = &rendered.image;
+        assert_eq!((image.width, image.height), (70, 120));
+        assert_eq!(image.pixels.len(), 70 * 120);
+        let values = finite_range(&frame.values).unwrap();
+        assert_eq!((rendered.min, rendered.max), values);
+        // The slice passes through an obstruction at y 3.8 to 4.6 and z 0
+        // to 0.4, which is at the bottom right of the image.
+        assert_eq!(image.get(40, 115), Some(options.obst_colour));
+        assert_ne!(image.get(5, 115), Some(options.obst_colour));
+        // The colour bar runs from the top of the range to the bottom.
+        assert_eq!(image.get(52, 0).unwrap().a, 0);
+        let (top, bottom) = (image.get(60, 0).unwrap(), image.get(60, 119).unwrap());
+        assert_eq!((top.r, top.b), (255, 0));
+        assert_eq!((bottom.r, bottom.b), (0, 255));
+
+        let mut png_data = Vec::new();
+        renderer.write_png(&mut png_data, frame, &options).unwrap();
+        let decoder = png::Decoder::new(std::io::Cursor::new(png_data));
+        let reader = decoder.read_info().unwrap();
+        assert_eq!((reader.info().width, reader.info().height), (70, 120));
+        let short = Frame {
+            time: 0.0,
+            values: vec![0.0; 3],
+        };
+        assert!(renderer.write_png(Vec::new(), &short, &options).is_err());
+
+        let options = RenderOptions {
+            colour_map: ColourMap::Viridis,
+            range: ValueRange::covering(&slice.frames[..5]),
+            scale: 20.0,
+            colour_bar: false,
+            ..RenderOptions::default()
+        };
+        let dir = std::env::temp_dir().join(format!("red-smv-png-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let paths = renderer
+            .write_png_series(&dir, "room_fire_01", &slice.frames[..5], &options)
+            .unwrap();
+        assert_eq!(paths.len(), 5);
+        assert!(dir.join("room_fire_01_0004.png").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,259 @@
+use crate::probe::{contains, value_positions};
+use crate::{Frame, GridRegion, Slcf, SmvMesh, Xb, Xyz};
+
+/// Summary statistics of the values of a slice frame that are selected by a
+/// [`SliceMask`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameStats {
+    pub time: f32,
+    /// The number of values selected.
+    pub count: usize,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f64,
+}
+
+/// The times at which any value selected by a [`SliceMask`] exceeds a
+/// threshold.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Exceedance {
+    /// The time of the first frame in which a value exceeds the threshold.
+    pub first_time: Option<f32>,
+    /// The total time for which a value exceeds the threshold. Each frame is
+    /// taken to hold until the time of the next frame, so the last frame does
+    /// not contribute.
+    pub duration: f64,
+}
+
+/// A selection of the values of a slice, such as those within a region of
+/// the mesh. Values are indexed in the same order as [`Frame::values`], with i
+/// varying fastest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SliceMask {
+    indices: Vec<usize>,
+}
+
+impl SliceMask {
+    /// Select every value of a slice.
+    pub fn all(slcf: &Slcf) -> Self {
+        Self::from_grid(slcf, slcf.ijk)
+    }
+
+    /// Select the values of a slice within a region of cells, given in the
+    /// grid coordinates of the mesh.
+    pub fn from_grid(slcf: &Slcf, region: GridRegion) -> Self {
+        let within = |a: i64, b: i64, v: i64| v >= a.min(b) && v <= a.max(b);
+        Self::select(slcf, |i, j, k, _| {
+            within(region.i1, region.i2, i)
+                && within(region.j1, region.j2, j)
+                && within(region.k1, region.k2, k)
+        })
+    }
+
+    /// Select the values of a slice that lie within a physical region of the
+    /// mesh. Returns None if the slice does not lie on the grid of the mesh.
+    pub fn from_xb(mesh: &SmvMesh, slcf: &Slcf, xb: Xb) -> Option<Self> {
+        let [xs, ys, zs] = value_positions(mesh, &slcf.ijk, slcf.cell_centred)?;
+        Some(Self::select(slcf, |_, _, _, [i, j, k]| {
+            contains(&xb, Xyz::new(xs[i], ys[j], zs[k]))
+        }))
+    }
+
+    /// Select the values for which `f` is true. `f` is given the grid
+    /// coordinates of each value and its position within the slice.
+    fn select<F: Fn(i64, i64, i64, [usize; 3]) -> bool>(slcf: &Slcf, f: F) -> Self {
+        let ijk = &slcf.ijk;
+        let mut indices = Vec::new();
+        let mut n = 0;
+        for (k_n, k) in (ijk.k1..=ijk.k2).enumerate() {
+            for (j_n, j) in (ijk.j1..=ijk.j2).enumerate() {
+                for (i_n, i) in (ijk.i1..=ijk.i2).enumerate() {
+                    if f(i, j, k, [i_n, j_n, k_n]) {
+                        indices.push(n);
+                    }
+                    n += 1;
+                }
+            }
+        }
+        Self { indices }
+    }
+
+    /// The indices of the selected values within a frame.
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// The selected values of a frame. Indices beyond the end of the frame
+    /// are skipped.
+    pub fn values<'a>(&'a self, frame: &'a Frame) -> impl Iterator<Item = f32> + 'a {
+        self.indices
+            .iter()
+            .filter_map(move |n| frame.values.get(*n).copied())
+    }
+
+    /// The statistics of the selected values of a frame. Returns None if no
+    /// values are selected.
+    pub fn stats(&self, frame: &Frame) -> Option<FrameStats> {
+        let mut values = self.values(frame);
+        let first = values.next()?;
+        let mut stats = FrameStats {
+            time: frame.time,
+            count: 1,
+            min: first,
+            max: first,
+            mean: first as f64,
+        };
+        for v in values {
+            stats.count += 1;
+            stats.min = stats.min.min(v);
+            stats.max = stats.max.max(v);
+            stats.mean += v as f64;
+        }
+        stats.mean /= stats.count as f64;
+        Some(stats)
+    }
+
+    /// The `p`th percentile (0 to 100) of the selected values of a frame,
+    /// interpolating linearly between the closest ranks. Returns None if no
+    /// values are selected or `p` is out of range.
+    pub fn percentile(&self, frame: &Frame, p: f64) -> Option<f32> {
+        if !(0.0..=100.0).contains(&p) {
+            return None;
+        }
+        let mut values: Vec<f32> = self.values(frame).collect();
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let rank = p / 100.0 * (values.len() - 1) as f64;
+        let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+        let w = rank - lower as f64;
+        Some((values[lower] as f64 * (1.0 - w) + values[upper] as f64 * w) as f32)
+    }
+
+    /// Whether any selected value of a frame exceeds a threshold.
+    pub fn exceeds(&self, frame: &Frame, threshold: f32) -> bool {
+        self.values(frame).any(|v| v > threshold)
+    }
+
+    /// The times at which any selected value exceeds a threshold over a
+    /// series of frames, which must be in time order. This is useful for
+    /// tenability, such as the time until the temperature at head height
+    /// exceeds 60°C.
+    pub fn exceedance<'a, I: IntoIterator<Item = &'a Frame>>(
+        &self,
+        frames: I,
+        threshold: f32,
+    ) -> Exceedance {
+        let mut result = Exceedance {
+            first_time: None,
+            duration: 0.0,
+        };
+        let mut exceeded_since: Option<f32> = None;
+        for frame in frames {
+            if let Some(t) = exceeded_since.take() {
+                result.duration += (frame.time - t) as f64;
+            }
+            if self.exceeds(frame, threshold) {
+                result.first_time.get_or_insert(frame.time);
+                exceeded_since = Some(frame.time);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_smv_file, SliceType};
+
+    fn test_slcf(ijk: GridRegion) -> Slcf {
+        Slcf {
+            cell_centred: false,
+            mesh: 1,
+            slice_type: SliceType::Structured,
+            id: None,
+            ijk,
+            index: None,
+            filename: "test_01.sf".to_string(),
+            long_name: "TEMPERATURE".to_string(),
+            short_name: "temp".to_string(),
+            units: "C".to_string(),
+        }
+    }
+
+    #[test]
+    fn grid_mask_stats() {
+        // A 3x1x2 slice at j = 5
+        let slcf = test_slcf(GridRegion::new(1, 3, 5, 5, 0, 1));
+        let frame = Frame {
+            time: 2.0,
+            values: vec![1., 2., 3., 4., 5., 6.],
+        };
+        let mask = SliceMask::from_grid(&slcf, GridRegion::new(2, 3, 0, 10, 1, 1));
+        assert_eq!(mask.indices(), &[4, 5]);
+        assert_eq!(
+            mask.stats(&frame),
+            Some(FrameStats {
+                time: 2.0,
+                count: 2,
+                min: 5.,
+                max: 6.,
+                mean: 5.5,
+            })
+        );
+        let all = SliceMask::all(&slcf);
+        assert_eq!(all.len(), 6);
+        assert_eq!(all.percentile(&frame, 0.), Some(1.));
+        assert_eq!(all.percentile(&frame, 50.), Some(3.5));
+        assert_eq!(all.percentile(&frame, 100.), Some(6.));
+        assert_eq!(all.percentile(&frame, 101.), None);
+        let none = SliceMask::from_grid(&slcf, GridRegion::new(4, 5, 5, 5, 0, 1));
+        assert!(none.is_empty());
+        assert_eq!(none.stats(&frame), None);
+    }
+
+    #[test]
+    fn exceedance() {
+        let slcf = test_slcf(GridRegion::new(0, 1, 0, 0, 0, 0));
+        let frames: Vec<Frame> = [(0.0, 20.), (1.0, 65.), (3.0, 70.), (4.0, 50.), (6.0, 61.)]
+            .iter()
+            .map(|(time, v)| Frame {
+                time: *time,
+                values: vec![20., *v],
+            })
+            .collect();
+        let result = SliceMask::all(&slcf).exceedance(&frames, 60.);
+        assert_eq!(result.first_time, Some(1.0));
+        assert_eq!(result.duration, 3.0);
+        let result = SliceMask::all(&slcf).exceedance(&frames, 100.);
+        assert_eq!(result.first_time, None);
+        assert_eq!(result.duration, 0.0);
+    }
+
+    #[test]
+    fn xb_mask() {
+        let smv = parse_smv_file(std::io::Cursor::new(include_str!("room_fire.smv"))).unwrap();
+        let slcf = &smv.slcfs[0];
+        let mesh = smv.slice_mesh(slcf).unwrap();
+        // The cell-centred slice spans j 0 to 10 and k 0 to 24, and cell 0 of
+        // each axis is outside the mesh. The cells below 1 m have centres
+        // 0.05 to 0.95.
+        let mask = SliceMask::from_xb(mesh, slcf, Xb::new(0.0, 10.0, 3.6, 4.6, 0.0, 1.0)).unwrap();
+        assert_eq!(mask.len(), 10 * 10);
+        let frames =
+            crate::parse_slice_file(&mut std::io::Cursor::new(include_bytes!("room_fire_01.sf")))
+                .unwrap()
+                .frames;
+        let stats = mask.stats(&frames[0]).unwrap();
+        assert_eq!(stats.count, 100);
+        assert!(stats.min <= stats.max);
+    }
+}
@@ -0,0 +1,482 @@
+use crate::probe::{value_positions, TOLERANCE};
+use crate::{Axis, Frame, ParseSliceError, Slcf, SliceParser, SliceType, SmvFile, Xyz};
+use std::io::{Read, Seek};
+
+/// The slices of one quantity that lie on the same plane. FDS splits a slice
+/// that crosses several meshes into a separate slice for each mesh.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlicePlane<'a> {
+    pub quantity: &'a str,
+    pub units: &'a str,
+    pub cell_centred: bool,
+    /// The axis normal to the plane.
+    pub axis: Axis,
+    /// The position of the plane along `axis`.
+    pub position: f64,
+    /// The slice in each mesh, in the order they appear in the SMV file.
+    pub slcfs: Vec<&'a Slcf>,
+}
+
+impl SmvFile {
+    /// Gather the structured plane slices into groups of the same quantity
+    /// on the same plane, across all meshes.
+    pub fn slice_planes(&self) -> Vec<SlicePlane<'_>> {
+        let mut planes: Vec<SlicePlane> = Vec::new();
+        let slcfs = self
+            .slcfs
+            .iter()
+            .filter(|slcf| slcf.slice_type == SliceType::Structured);
+        for slcf in slcfs {
+            let (axis, xb) = match (slcf.plane(), self.slice_xb(slcf)) {
+                (Some(axis), Some(xb)) => (axis, xb),
+                _ => continue,
+            };
+            let position = component(Xyz::new(xb.x1, xb.y1, xb.z1), axis);
+            let existing = planes.iter_mut().find(|plane| {
+                plane.quantity == slcf.long_name
+                    && plane.units == slcf.units
+                    && plane.cell_centred == slcf.cell_centred
+                    && plane.axis == axis
+                    && (plane.position - position).abs() <= TOLERANCE
+            });
+            match existing {
+                Some(plane) => plane.slcfs.push(slcf),
+                None => planes.push(SlicePlane {
+                    quantity: &slcf.long_name,
+                    units: &slcf.units,
+                    cell_centred: slcf.cell_centred,
+                    axis,
+                    position,
+                    slcfs: vec![slcf],
+                }),
+            }
+        }
+        planes
+    }
+}
+
+/// The global grid of a [`SlicePlane`], which combines the slices in each
+/// mesh into a single 2D field.
+///
+/// The coordinates along each axis in the plane are the positions of the
+/// values of every mesh, merged. Values that lie outside their mesh, such as
+/// the ghost cells of cell-centred slices, are dropped. Each position takes
+/// the nearest value of the slice that covers it, so where meshes have
+/// different resolutions the coarser mesh fills the positions of the finer
+/// one. Positions covered by more than one slice, as on the boundary between
+/// meshes, take the average. Positions not covered by any slice have the
+/// value NaN.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StitchedGrid {
+    pub quantity: String,
+    pub units: String,
+    /// The axis normal to the plane.
+    pub axis: Axis,
+    pub position: f64,
+    /// The axes in the plane, in the order x, y, z.
+    pub u_axis: Axis,
+    pub v_axis: Axis,
+    /// The coordinates of the grid along `u_axis` and `v_axis`.
+    pub u: Vec<f64>,
+    pub v: Vec<f64>,
+    /// For each slice, the index of each value used and the index in the
+    /// global grid it is placed at.
+    parts: Vec<Vec<(usize, usize)>>,
+}
+
+impl StitchedGrid {
+    /// Returns None if the mesh of a slice is not in the SMV file or a slice
+    /// does not lie on the grid of its mesh.
+    pub fn new(smv: &SmvFile, plane: &SlicePlane) -> Option<Self> {
+        let (u_axis, v_axis) = plane_axes(plane.axis);
+        let mut slices = Vec::with_capacity(plane.slcfs.len());
+        for slcf in &plane.slcfs {
+            slices.push(PlaneSlice::new(smv, slcf, u_axis, v_axis)?);
+        }
+        let u = merge_axis(slices.iter().flat_map(|slice| positions(&slice.u)));
+        let v = merge_axis(slices.iter().flat_map(|slice| positions(&slice.v)));
+        let parts = slices
+            .iter()
+            .map(|slice| {
+                let mut part = Vec::new();
+                for (j, pv) in v.iter().enumerate() {
+                    for (i, pu) in u.iter().enumerate() {
+                        if let Some(n) = slice.nearest(*pu, *pv) {
+                            part.push((n, i + u.len() * j));
+                        }
+                    }
+                }
+                part
+            })
+            .collect();
+        Some(Self {
+            quantity: plane.quantity.to_string(),
+            units: plane.units.to_string(),
+            axis: plane.axis,
+            position: plane.position,
+            u_axis,
+            v_axis,
+            u,
+            v,
+            parts,
+        })
+    }
+
+    /// The number of values in a stitched frame, with u varying fastest.
+    pub fn n_values(&self) -> usize {
+        self.u.len() * self.v.len()
+    }
+
+    /// Combine a frame from each slice, in the order of
+    /// [`SlicePlane::slcfs`], into a frame of the global grid. The time is
+    /// taken from the first frame. Values beyond the end of a frame are
+    /// treated as missing.
+    pub fn stitch<'a, I: IntoIterator<Item = &'a Frame>>(&self, frames: I) -> Frame {
+        let mut sums = vec![0.0_f64; self.n_values()];
+        let mut counts = vec![0_u32; self.n_values()];
+        let mut time = None;
+        for (part, frame) in self.parts.iter().zip(frames) {
+            time.get_or_insert(frame.time);
+            for (n, target) in part {
+                if let Some(value) = frame.values.get(*n) {
+                    sums[*target] += *value as f64;
+                    counts[*target] += 1;
+                }
+            }
+        }
+        let values = sums
+            .iter()
+            .zip(&counts)
+            .map(|(sum, count)| {
+                if *count > 0 {
+                    (sum / *count as f64) as f32
+                } else {
+                    f32::NAN
+                }
+            })
+            .collect();
+        Frame {
+            time: time.unwrap_or_default(),
+            values,
+        }
+    }
+
+    /// Read the frames of each slice, in the order of [`SlicePlane::slcfs`],
+    /// and stitch them together. Frames are combined in the order they are
+    /// read, as FDS writes the slices of every mesh at the same times. Reading
+    /// ends when any slice has no more frames.
+    pub fn read_frames<R: Read + Seek>(
+        &self,
+        mut parsers: Vec<SliceParser<R>>,
+    ) -> Result<Vec<Frame>, ParseSliceError> {
+        let mut frames = Vec::new();
+        loop {
+            let mut parts = Vec::with_capacity(parsers.len());
+            for parser in parsers.iter_mut() {
+                match parser.next() {
+                    Some(frame) => parts.push(frame?),
+                    None => return Ok(frames),
+                }
+            }
+            frames.push(self.stitch(&parts));
+        }
+    }
+}
+
+/// The values of a slice in a [`SlicePlane`] which lie within its mesh.
+struct PlaneSlice {
+    /// The bounds of the slice along the u and v axes.
+    u_bounds: (f64, f64),
+    v_bounds: (f64, f64),
+    /// The positions of the values along the u and v axes, in increasing
+    /// order, with the step in the index of a value for that position.
+    u: Vec<(usize, f64)>,
+    v: Vec<(usize, f64)>,
+}
+
+impl PlaneSlice {
+    fn new(smv: &SmvFile, slcf: &Slcf, u_axis: Axis, v_axis: Axis) -> Option<Self> {
+        let mesh = smv.slice_mesh(slcf)?;
+        let xb = smv.slice_xb(slcf)?;
+        let xyz = value_positions(mesh, &slcf.ijk, slcf.cell_centred)?;
+        // The values are ordered with x varying fastest, then y.
+        let stride = |axis: Axis| match axis {
+            Axis::X => 1,
+            Axis::Y => xyz[0].len(),
+            Axis::Z => xyz[0].len() * xyz[1].len(),
+        };
+        let within_mesh = |axis: Axis| {
+            let (a, b) = (
+                component(Xyz::new(mesh.dims.x1, mesh.dims.y1, mesh.dims.z1), axis),
+                component(Xyz::new(mesh.dims.x2, mesh.dims.y2, mesh.dims.z2), axis),
+            );
+            let positions = &xyz[axis_number(axis)];
+            positions
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| **p >= a.min(b) - TOLERANCE && **p <= a.max(b) + TOLERANCE)
+                .map(|(n, p)| (n * stride(axis), *p))
+                .collect::<Vec<_>>()
+        };
+        let bounds = |axis: Axis| {
+            let (a, b) = (
+                component(Xyz::new(xb.x1, xb.y1, xb.z1), axis),
+                component(Xyz::new(xb.x2, xb.y2, xb.z2), axis),
+            );
+            (a.min(b), a.max(b))
+        };
+        Some(Self {
+            u_bounds: bounds(u_axis),
+            v_bounds: bounds(v_axis),
+            u: within_mesh(u_axis),
+            v: within_mesh(v_axis),
+        })
+    }
+
+    /// The index of the value nearest to a position, or None if the position
+    /// is outside the slice.
+    fn nearest(&self, u: f64, v: f64) -> Option<usize> {
+        let covers = |(a, b): (f64, f64), p: f64| p >= a - TOLERANCE && p <= b + TOLERANCE;
+        if !covers(self.u_bounds, u) || !covers(self.v_bounds, v) {
+            return None;
+        }
+        Some(nearest(&self.u, u)? + nearest(&self.v, v)?)
+    }
+}
+
+fn positions(axis: &[(usize, f64)]) -> impl Iterator<Item = f64> + '_ {
+    axis.iter().map(|(_, p)| *p)
+}
+
+/// The step of the position in `axis` nearest to `position`. Positions
+/// equally near within [`TOLERANCE`] resolve to the lower one.
+fn nearest(axis: &[(usize, f64)], position: f64) -> Option<usize> {
+    let n = axis.partition_point(|(_, p)| *p < position);
+    let after = axis.get(n);
+    let before = n.checked_sub(1).and_then(|n| axis.get(n));
+    match (before, after) {
+        (Some(before), Some(after)) if after.1 - position < position - before.1 - TOLERANCE => {
+            Some(after.0)
+        }
+        (Some(before), _) => Some(before.0),
+        (None, after) => after.map(|after| after.0),
+    }
+}
+
+fn axis_number(axis: Axis) -> usize {
+    match axis {
+        Axis::X => 0,
+        Axis::Y => 1,
+        Axis::Z => 2,
+    }
+}
+
+/// A [`SlicePlane`] read from every mesh and stitched into a single field.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StitchedSlice {
+    pub grid: StitchedGrid,
+    pub frames: Vec<Frame>,
+}
+
+/// The axes in a plane normal to `axis`, in the order x, y, z.
+pub(crate) fn plane_axes(axis: Axis) -> (Axis, Axis) {
+    match axis {
+        Axis::X => (Axis::Y, Axis::Z),
+        Axis::Y => (Axis::X, Axis::Z),
+        Axis::Z => (Axis::X, Axis::Y),
+    }
+}
+
+pub(crate) fn component(xyz: Xyz, axis: Axis) -> f64 {
+    match axis {
+        Axis::X => xyz.x,
+        Axis::Y => xyz.y,
+        Axis::Z => xyz.z,
+    }
+}
+
+/// Sort positions and merge those within [`TOLERANCE`] of each other.
+fn merge_axis<I: Iterator<Item = f64>>(positions: I) -> Vec<f64> {
+    let mut positions: Vec<f64> = positions.collect();
+    positions.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    positions.dedup_by(|b, a| *b - *a <= TOLERANCE);
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_smv_file, SliceWriter};
+
+    /// The index of a position in an axis created by [`merge_axis`] which
+    /// includes it.
+    fn axis_index(axis: &[f64], position: f64) -> usize {
+        axis.partition_point(|p| *p < position - TOLERANCE)
+    }
+
+    /// A frame for a slice where each value is a function of its position.
+    fn test_frame(smv: &SmvFile, slcf: &Slcf, f: impl Fn(f64, f64) -> f32) -> Frame {
+        let mesh = smv.slice_mesh(slcf).unwrap();
+        let [xs, ys, zs] = value_positions(mesh, &slcf.ijk, slcf.cell_centred).unwrap();
+        let mut values = Vec::new();
+        for z in &zs {
+            for y in &ys {
+                for _ in &xs {
+                    values.push(f(*y, *z));
+                }
+            }
+        }
+        Frame { time: 1.0, values }
+    }
+
+    #[test]
+    fn stitch_test1_plane() {
+        let smv = parse_smv_file(std::io::Cursor::new(include_str!("test1.smv"))).unwrap();
+        let planes = smv.slice_planes();
+        let plane = planes
+            .iter()
+            .find(|plane| plane.quantity == "TEMPERATURE" && plane.axis == Axis::X)
+            .unwrap();
+        assert!((plane.position - 31.9).abs() < 1e-6);
+        let meshes: Vec<usize> = plane.slcfs.iter().map(|slcf| slcf.mesh).collect();
+        assert_eq!(meshes, vec![1, 2, 3, 4, 5, 6]);
+
+        let grid = StitchedGrid::new(&smv, plane).unwrap();
+        assert_eq!((grid.u_axis, grid.v_axis), (Axis::Y, Axis::Z));
+        assert!((grid.u[0] - -3.0).abs() < 1e-6);
+        assert!((grid.u[grid.u.len() - 1] - 16.1).abs() < 1e-6);
+        assert!((grid.v[0] - 0.0).abs() < 1e-6);
+        assert!((grid.v[grid.v.len() - 1] - 15.9).abs() < 1e-6);
+
+        let f = |y: f64, z: f64| (y + 100.0 * z) as f32;
+        let frames: Vec<Frame> = plane
+            .slcfs
+            .iter()
+            .map(|slcf| test_frame(&smv, slcf, f))
+            .collect();
+        let stitched = grid.stitch(&frames);
+        assert_eq!(stitched.values.len(), grid.n_values());
+        let mut covered = 0;
+        for (n, value) in stitched.values.iter().enumerate() {
+            if value.is_nan() {
+                continue;
+            }
+            covered += 1;
+            let (u, v) = (grid.u[n % grid.u.len()], grid.v[n / grid.u.len()]);
+            assert!((value - f(u, v)).abs() < 1e-3);
+        }
+        // The corridor (y 7.3 to 9.1, z 3.8 to 6.3) is covered.
+        let at = |u: f64, v: f64| {
+            stitched.values[axis_index(&grid.u, u) + grid.u.len() * axis_index(&grid.v, v)]
+        };
+        assert!(!at(8.0, 5.0).is_nan());
+        // Nothing covers y = 8 below the corridor.
+        assert!(at(8.0, 1.0).is_nan());
+        assert!(covered < grid.n_values());
+
+        let parsers = frames
+            .iter()
+            .zip(&plane.slcfs)
+            .map(|(frame, slcf)| {
+                let ijk = slcf.ijk;
+                let header = crate::SliceHeader {
+                    quantity: "TEMPERATURE".to_string(),
+                    short_name: "temp".to_string(),
+                    units: "C".to_string(),
+                    dimensions: crate::Dimensions {
+                        i_min: ijk.i1 as u32,
+                        i_max: ijk.i2 as u32,
+                        j_min: ijk.j1 as u32,
+                        j_max: ijk.j2 as u32,
+                        k_min: ijk.k1 as u32,
+                        k_max: ijk.k2 as u32,
+                    },
+                };
+                let mut writer = SliceWriter::new(Vec::new(), header).unwrap();
+                writer.write_frame(frame).unwrap();
+                writer.write_frame(frame).unwrap();
+                SliceParser::new(std::io::Cursor::new(writer.into_inner())).unwrap()
+            })
+            .collect();
+        let read = grid.read_frames(parsers).unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(
+            read[1].values.iter().filter(|v| !v.is_nan()).count(),
+            covered
+        );
+    }
+
+    #[test]
+    fn stitch_mixed_resolution() {
+        // The room mesh, with 0.1 m cells, and a mesh beside it in x with
+        // 0.2 m cells.
+        let mut smv = parse_smv_file(std::io::Cursor::new(include_str!("room_fire.smv"))).unwrap();
+        smv.slcfs.clear();
+        let mut coarse = smv.meshes[0].clone();
+        let trn = |start: f64, n: usize| -> Vec<crate::TrnEntry> {
+            (0..=n)
+                .map(|i| crate::TrnEntry {
+                    i,
+                    f: start + 0.2 * i as f64,
+                })
+                .collect()
+        };
+        coarse.trnx = trn(3.5, 12);
+        coarse.trny = trn(3.6, 5);
+        coarse.trnz = trn(0.0, 12);
+        coarse.dims = crate::Xb::new(3.5, 5.9, 3.6, 4.6, 0.0, 2.4);
+        coarse.obsts.clear();
+        smv.meshes.push(coarse);
+
+        // Planes at y = 4.0.
+        for (mesh, n, j) in [(1, 24, 4), (2, 12, 2)].iter() {
+            let header = crate::SliceHeader {
+                quantity: "TEMPERATURE".to_string(),
+                short_name: "temp".to_string(),
+                units: "C".to_string(),
+                dimensions: crate::Dimensions {
+                    i_min: 0,
+                    i_max: *n,
+                    j_min: *j,
+                    j_max: *j,
+                    k_min: 0,
+                    k_max: *n,
+                },
+            };
+            smv.add_slice(*mesh, "test.sf", &header, false).unwrap();
+        }
+        let planes = smv.slice_planes();
+        assert_eq!(planes.len(), 1);
+        let grid = StitchedGrid::new(&smv, &planes[0]).unwrap();
+        assert_eq!((grid.u.len(), grid.v.len()), (37, 25));
+
+        let f = |x: f64, z: f64| (x + 10.0 * z) as f32;
+        let frames: Vec<Frame> = planes[0]
+            .slcfs
+            .iter()
+            .map(|slcf| {
+                let mesh = smv.slice_mesh(slcf).unwrap();
+                let [xs, _, zs] = value_positions(mesh, &slcf.ijk, false).unwrap();
+                let mut values = Vec::new();
+                for z in &zs {
+                    for x in &xs {
+                        values.push(f(*x, *z));
+                    }
+                }
+                Frame { time: 1.0, values }
+            })
+            .collect();
+        let stitched = grid.stitch(&frames);
+        assert!(stitched.values.iter().all(|v| !v.is_nan()));
+        let at = |u: f64, v: f64| {
+            stitched.values[axis_index(&grid.u, u) + grid.u.len() * axis_index(&grid.v, v)]
+        };
+        assert!((at(2.0, 1.1) - f(2.0, 1.1)).abs() < 1e-4);
+        assert!((at(4.3, 1.2) - f(4.3, 1.2)).abs() < 1e-4);
+        // Between the values of the coarse mesh the lower one is used.
+        assert!((at(4.3, 1.1) - f(4.3, 1.0)).abs() < 1e-4);
+        // On the boundary between the meshes the values are averaged.
+        let boundary = (f(3.5, 1.1) + f(3.5, 1.0)) / 2.0;
+        assert!((at(3.5, 1.1) - boundary).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,213 @@
+use crate::{Frame, RecordFormat, Slcf, SliceHeader, SliceType, SmvFile};
+use std::io::Write;
+
+/// The length in bytes of the quantity, short name and units in the header of
+/// a slice file.
+const LABEL_LENGTH: usize = 30;
+
+/// Writes a slice file (.sf) in the format read by [`crate::SliceParser`]
+/// and Smokeview. The header is written when the writer is created, then
+/// frames are written one at a time.
+///
+/// The header strings are padded with spaces or truncated to 30 bytes, as
+/// FDS writes them and Smokeview expects.
+#[derive(Debug)]
+pub struct SliceWriter<W: Write> {
+    writer: W,
+    header: SliceHeader,
+    format: RecordFormat,
+}
+
+impl<W: Write> SliceWriter<W> {
+    /// Create a writer using the default [`RecordFormat`] and write the
+    /// header.
+    pub fn new(writer: W, header: SliceHeader) -> std::io::Result<Self> {
+        Self::with_format(writer, header, RecordFormat::default())
+    }
+
+    /// Create a writer using the given [`RecordFormat`] and write the header.
+    pub fn with_format(
+        mut writer: W,
+        mut header: SliceHeader,
+        format: RecordFormat,
+    ) -> std::io::Result<Self> {
+        header.quantity = pad_label(&header.quantity);
+        header.short_name = pad_label(&header.short_name);
+        header.units = pad_label(&header.units);
+        format.write_record(&mut writer, header.quantity.as_bytes())?;
+        format.write_record(&mut writer, header.short_name.as_bytes())?;
+        format.write_record(&mut writer, header.units.as_bytes())?;
+        let d = header.dimensions;
+        let dimensions = [d.i_min, d.i_max, d.j_min, d.j_max, d.k_min, d.k_max];
+        let dimensions: Vec<i32> = dimensions.iter().map(|v| *v as i32).collect();
+        format.write_record(&mut writer, &format.encode_i32s(&dimensions))?;
+        Ok(Self {
+            writer,
+            header,
+            format,
+        })
+    }
+
+    /// The header as written, with the labels padded or truncated.
+    pub fn header(&self) -> &SliceHeader {
+        &self.header
+    }
+
+    pub fn format(&self) -> RecordFormat {
+        self.format
+    }
+
+    /// Write a frame. Frames should be written in time order. Returns an
+    /// error of kind `InvalidInput` if the frame does not have one value for
+    /// each point of the slice.
+    pub fn write_frame(&mut self, frame: &Frame) -> std::io::Result<()> {
+        let n_values = self.header.dimensions.n_values();
+        if frame.values.len() as u64 != n_values {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "slice frame has {} values but the slice has {}",
+                    frame.values.len(),
+                    n_values
+                ),
+            ));
+        }
+        self.format
+            .write_record(&mut self.writer, &self.format.encode_f32s(&[frame.time]))?;
+        self.format
+            .write_record(&mut self.writer, &self.format.encode_f32s(&frame.values))
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Return the underlying writer. It is not flushed.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Pad a label with spaces or truncate it to [`LABEL_LENGTH`] bytes, without
+/// splitting a character.
+fn pad_label(label: &str) -> String {
+    let mut end = label.len().min(LABEL_LENGTH);
+    while !label.is_char_boundary(end) {
+        end -= 1;
+    }
+    let mut padded = label[..end].to_string();
+    padded.push_str(&" ".repeat(LABEL_LENGTH - end));
+    padded
+}
+
+impl SmvFile {
+    /// Add an SLCF entry for a slice file written with [`SliceWriter`], so
+    /// that it is listed by Smokeview once the SMV file is written. `mesh` is
+    /// the 1-based index of the mesh the slice is in, and `filename` is
+    /// relative to the SMV file. The slice is given the next unused index.
+    /// Returns None if there is no such mesh.
+    pub fn add_slice(
+        &mut self,
+        mesh: usize,
+        filename: &str,
+        header: &SliceHeader,
+        cell_centred: bool,
+    ) -> Option<&Slcf> {
+        self.meshes.get(mesh.checked_sub(1)?)?;
+        let index = self
+            .slcfs
+            .iter()
+            .filter_map(|slcf| slcf.index)
+            .max()
+            .map_or(1, |index| index + 1);
+        self.slcfs.push(Slcf {
+            cell_centred,
+            mesh,
+            slice_type: SliceType::Structured,
+            id: None,
+            ijk: header.dimensions.into(),
+            index: Some(index),
+            filename: filename.to_string(),
+            long_name: header.quantity.trim().to_string(),
+            short_name: header.short_name.trim().to_string(),
+            units: header.units.trim().to_string(),
+        });
+        self.slcfs.last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_slice_file, parse_smv_file, Endianness, MarkerWidth, SliceParser};
+
+    #[test]
+    fn slice_round_trip() {
+        let original = include_bytes!("room_fire_01.sf");
+        let slice = parse_slice_file(&mut std::io::Cursor::new(original)).unwrap();
+        let mut writer = SliceWriter::new(Vec::new(), slice.header.clone()).unwrap();
+        for frame in &slice.frames {
+            writer.write_frame(frame).unwrap();
+        }
+        assert_eq!(writer.into_inner(), original.to_vec());
+
+        let format = RecordFormat::new(Endianness::Big, MarkerWidth::Eight);
+        let mut writer =
+            SliceWriter::with_format(Vec::new(), slice.header.clone(), format).unwrap();
+        writer.write_frame(&slice.frames[3]).unwrap();
+        let short = Frame {
+            time: 0.0,
+            values: vec![0.0; 3],
+        };
+        assert!(writer.write_frame(&short).is_err());
+        let mut parser = SliceParser::new(std::io::Cursor::new(writer.into_inner())).unwrap();
+        assert_eq!(parser.format(), format);
+        assert_eq!(parser.header, slice.header);
+        assert_eq!(parser.next().unwrap().unwrap(), slice.frames[3]);
+        assert!(parser.next().is_none());
+    }
+
+    #[test]
+    fn slice_labels() {
+        let mut header =
+            parse_slice_file(&mut std::io::Cursor::new(include_bytes!("room_fire_01.sf")))
+                .unwrap()
+                .header;
+        header.quantity = "TEMPERATURE".to_string();
+        header.short_name = "temp".repeat(10);
+        header.units = "°C".to_string();
+        let writer = SliceWriter::new(Vec::new(), header).unwrap();
+        let written = writer.header().clone();
+        assert_eq!(written.quantity, format!("{:<30}", "TEMPERATURE"));
+        assert_eq!(written.short_name, "temp".repeat(10)[..30]);
+        assert_eq!(written.units.len(), 30);
+        assert_eq!(written.units.trim(), "°C");
+        assert_eq!(pad_label(&"°".repeat(20)).len(), 30);
+        let parser = SliceParser::new(std::io::Cursor::new(writer.into_inner())).unwrap();
+        assert_eq!(parser.header, written);
+    }
+
+    #[test]
+    fn add_slice() {
+        let mut smv = parse_smv_file(std::io::Cursor::new(include_str!("room_fire.smv"))).unwrap();
+        let header = parse_slice_file(&mut std::io::Cursor::new(include_bytes!("room_fire_01.sf")))
+            .unwrap()
+            .header;
+        assert!(smv
+            .add_slice(2, "room_fire_diff.sf", &header, true)
+            .is_none());
+        let slcf = smv
+            .add_slice(1, "room_fire_diff.sf", &header, true)
+            .unwrap()
+            .clone();
+        assert_eq!(slcf.ijk, smv.slcfs[0].ijk);
+        assert_eq!(slcf.long_name, "TEMPERATURE");
+        // The largest index in the file is 7.
+        assert_eq!(slcf.index, Some(8));
+
+        let mut output = Vec::new();
+        smv.write_to(&mut output).unwrap();
+        let smv = parse_smv_file(std::io::Cursor::new(output)).unwrap();
+        assert_eq!(smv.slcfs.last(), Some(&slcf));
+    }
+}
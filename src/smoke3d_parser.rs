@@ -0,0 +1,413 @@
+use crate::fortran_record::{next_frame, FrameOffsets};
+use crate::{Dimensions, RecordError, RecordFormat};
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Seek;
+
+/// The marker byte which starts a run in the run-length encoding used by FDS.
+const RLE_MARK: u8 = 255;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Smoke3dFile {
+    pub header: Smoke3dHeader,
+    pub frames: Vec<Smoke3dFrame>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Smoke3dHeader {
+    /// The compression used for frames. FDS writes version 0, which is
+    /// run-length encoded.
+    pub version: i32,
+    /// The grid nodes of the mesh.
+    pub dimensions: Dimensions,
+}
+
+impl Smoke3dHeader {
+    pub fn n_values(&self) -> u64 {
+        self.dimensions.n_values()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Smoke3dFrame {
+    pub time: f32,
+    /// The decoded value at each grid node, with i varying fastest. For soot
+    /// these are opacities and for other quantities (e.g. HRRPUV) the value
+    /// scaled to a byte by FDS.
+    pub values: Vec<u8>,
+}
+
+/// An entry in the size (.sz) file written alongside a 3D smoke file, which
+/// gives the length of each frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Smoke3dSize {
+    pub time: f32,
+    /// The number of values in the frame after decoding.
+    pub n_chars_in: u32,
+    /// The number of bytes of encoded data in the frame.
+    pub n_chars_out: u32,
+}
+
+#[derive(Debug)]
+pub enum ParseSmoke3dError {
+    Record(RecordError),
+    /// The size file could not be read.
+    IOError(std::io::Error),
+    /// The header does not start with the integer 1, or has a negative
+    /// dimension or a maximum less than its minimum.
+    InvalidHeader,
+    /// The frames are compressed with something other than run-length
+    /// encoding.
+    UnsupportedVersion(i32),
+    /// The frame sizes are negative, or the decoded frame did not have the
+    /// expected number of values.
+    InvalidFrame,
+    /// A line of the size file could not be parsed.
+    InvalidSize(String),
+}
+
+impl std::fmt::Display for ParseSmoke3dError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Record(err) => write!(f, "could not read 3D smoke file: {}", err),
+            Self::IOError(err) => write!(f, "could not read 3D smoke size file: {}", err),
+            Self::InvalidHeader => write!(f, "3D smoke file has an invalid header"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "3D smoke file version {} is not supported", version)
+            }
+            Self::InvalidFrame => write!(f, "3D smoke frame has the wrong number of values"),
+            Self::InvalidSize(line) => write!(f, "invalid 3D smoke size entry: {:?}", line),
+        }
+    }
+}
+
+impl std::error::Error for ParseSmoke3dError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            Self::Record(ref e) => Some(e),
+            Self::IOError(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<RecordError> for ParseSmoke3dError {
+    fn from(err: RecordError) -> Self {
+        Self::Record(err)
+    }
+}
+
+/// A reader for 3D smoke (.s3d) files. As frames are compressed they have no
+/// fixed length, so the offsets of frames are either taken from the size
+/// (.sz) file or recorded as they are found when seeking.
+#[derive(Debug)]
+pub struct Smoke3dParser<R> {
+    reader: BufReader<R>,
+    pub header: Smoke3dHeader,
+    frame_offsets: FrameOffsets,
+    format: RecordFormat,
+}
+
+impl<R: Read> Smoke3dParser<R> {
+    pub fn parse_frame(&mut self) -> Result<Smoke3dFrame, ParseSmoke3dError> {
+        parse_smoke3d_frame(self.format, &self.header, &mut self.reader)
+    }
+    pub fn header_length(&self) -> u64 {
+        self.frame_offsets.header_length()
+    }
+    /// The record format of the file, which is detected from the header.
+    pub fn format(&self) -> RecordFormat {
+        self.format
+    }
+}
+
+impl<R: Read + Seek> Smoke3dParser<R> {
+    pub fn new(input: R) -> Result<Self, ParseSmoke3dError> {
+        let mut reader = BufReader::new(input);
+        let format = RecordFormat::detect(&mut reader).map_err(RecordError::IOError)?;
+        let header = parse_smoke3d_header(format, &mut reader)?;
+        let header_length = reader.stream_position().map_err(RecordError::IOError)?;
+        Ok(Smoke3dParser {
+            reader,
+            header,
+            frame_offsets: FrameOffsets::new(header_length),
+            format,
+        })
+    }
+    /// Create a parser with the offsets of all frames calculated from the
+    /// entries of the size file, so any frame can be read directly.
+    pub fn with_sizes(input: R, sizes: &[Smoke3dSize]) -> Result<Self, ParseSmoke3dError> {
+        let mut parser = Self::new(input)?;
+        for size in sizes {
+            parser
+                .frame_offsets
+                .push_length(frame_length(parser.format, size.n_chars_out));
+        }
+        Ok(parser)
+    }
+    /// Seek to the start of a frame, returning its offset.
+    pub fn seek_frame(&mut self, frame: usize) -> Result<u64, ParseSmoke3dError> {
+        let format = self.format;
+        self.frame_offsets.seek(&mut self.reader, frame, |reader| {
+            skip_smoke3d_frame(format, reader)
+        })
+    }
+    pub fn get_frame(&mut self, frame: usize) -> Result<Smoke3dFrame, ParseSmoke3dError> {
+        self.seek_frame(frame)?;
+        self.parse_frame()
+    }
+}
+
+impl<R: Read> Iterator for Smoke3dParser<R> {
+    type Item = Result<Smoke3dFrame, ParseSmoke3dError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let format = self.format;
+        let header = &self.header;
+        next_frame(&mut self.reader, |reader| {
+            parse_smoke3d_frame(format, header, reader)
+        })
+    }
+}
+
+pub fn parse_smoke3d_file<R: Read + Seek>(i: &mut R) -> Result<Smoke3dFile, ParseSmoke3dError> {
+    let parser = Smoke3dParser::new(i)?;
+    let header = parser.header;
+    let frames = parser.collect::<Result<Vec<_>, _>>()?;
+    Ok(Smoke3dFile { header, frames })
+}
+
+/// Parse the size (.sz) file of a 3D smoke file. The first line holds the
+/// version, followed by a line for each frame.
+pub fn parse_smoke3d_sizes<R: BufRead>(input: R) -> Result<Vec<Smoke3dSize>, ParseSmoke3dError> {
+    let mut sizes = Vec::new();
+    for line in input.lines().skip(1) {
+        let line = line.map_err(ParseSmoke3dError::IOError)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let size =
+            parse_size_line(&line).ok_or_else(|| ParseSmoke3dError::InvalidSize(line.clone()))?;
+        sizes.push(size);
+    }
+    Ok(sizes)
+}
+
+fn parse_size_line(line: &str) -> Option<Smoke3dSize> {
+    let mut values = line.split_whitespace();
+    Some(Smoke3dSize {
+        time: values.next()?.parse().ok()?,
+        n_chars_in: values.next()?.parse().ok()?,
+        n_chars_out: values.next()?.parse().ok()?,
+    })
+}
+
+pub fn parse_smoke3d_frame<R: Read>(
+    format: RecordFormat,
+    header: &Smoke3dHeader,
+    mut i: R,
+) -> Result<Smoke3dFrame, ParseSmoke3dError> {
+    let time = format.decode_f32s(&format.read_record_expecting(&mut i, 4)?)?[0];
+    let (n_chars_in, n_chars_out) = parse_frame_sizes(format, &mut i)?;
+    if n_chars_in as u64 != header.n_values() {
+        return Err(ParseSmoke3dError::InvalidFrame);
+    }
+    let encoded = if n_chars_out > 0 {
+        format.read_record_expecting(&mut i, n_chars_out as u64)?
+    } else {
+        vec![]
+    };
+    let values = decode_rle(&encoded);
+    if values.len() as u64 != header.n_values() {
+        return Err(ParseSmoke3dError::InvalidFrame);
+    }
+    Ok(Smoke3dFrame { time, values })
+}
+
+/// Decode run-length encoded data as written by FDS. A run is written as the
+/// marker byte followed by the value and the length of the run; any other
+/// byte is a single value.
+pub fn decode_rle(encoded: &[u8]) -> Vec<u8> {
+    let mut values = Vec::with_capacity(encoded.len());
+    let mut bytes = encoded.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte == RLE_MARK {
+            match (bytes.next(), bytes.next()) {
+                (Some(&value), Some(&n)) => {
+                    values.resize(values.len() + n as usize, value);
+                }
+                // A truncated run is ignored, which will be caught by the
+                // length check on the decoded frame.
+                _ => break,
+            }
+        } else {
+            values.push(byte);
+        }
+    }
+    values
+}
+
+/// The length in bytes of a frame with the given amount of encoded data.
+fn frame_length(format: RecordFormat, n_chars_out: u32) -> u64 {
+    // The time record and the size record
+    let length = format.record_length(4) + format.record_length(8);
+    if n_chars_out > 0 {
+        length + format.record_length(n_chars_out as u64)
+    } else {
+        length
+    }
+}
+
+/// Skip over a frame without decoding the data.
+fn skip_smoke3d_frame<R: Read + Seek>(
+    format: RecordFormat,
+    i: &mut BufReader<R>,
+) -> Result<(), ParseSmoke3dError> {
+    i.seek_relative(format.record_length(4) as i64)
+        .map_err(RecordError::IOError)?;
+    let (_, n_chars_out) = parse_frame_sizes(format, i)?;
+    // The time and size records have already been read
+    let remaining = frame_length(format, n_chars_out) - frame_length(format, 0);
+    i.seek_relative(remaining as i64)
+        .map_err(RecordError::IOError)?;
+    Ok(())
+}
+
+fn parse_frame_sizes<R: Read>(
+    format: RecordFormat,
+    i: &mut R,
+) -> Result<(u32, u32), ParseSmoke3dError> {
+    match format
+        .decode_i32s(&format.read_record_expecting(i, 8)?)?
+        .as_slice()
+    {
+        [n_in, n_out] if *n_in >= 0 && *n_out >= 0 => Ok((*n_in as u32, *n_out as u32)),
+        _ => Err(ParseSmoke3dError::InvalidFrame),
+    }
+}
+
+fn parse_smoke3d_header<R: Read>(
+    format: RecordFormat,
+    i: &mut R,
+) -> Result<Smoke3dHeader, ParseSmoke3dError> {
+    let values = format.decode_i32s(&format.read_record_expecting(i, 32)?)?;
+    if values[0] != 1 {
+        return Err(ParseSmoke3dError::InvalidHeader);
+    }
+    let version = values[1];
+    if version != 0 {
+        return Err(ParseSmoke3dError::UnsupportedVersion(version));
+    }
+    if values[2..].iter().any(|v| *v < 0) {
+        return Err(ParseSmoke3dError::InvalidHeader);
+    }
+    let dimensions = Dimensions {
+        i_min: values[2] as u32,
+        i_max: values[3] as u32,
+        j_min: values[4] as u32,
+        j_max: values[5] as u32,
+        k_min: values[6] as u32,
+        k_max: values[7] as u32,
+    };
+    if !dimensions.is_valid() {
+        return Err(ParseSmoke3dError::InvalidHeader);
+    }
+    Ok(Smoke3dHeader {
+        version,
+        dimensions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fortran_record::test_records::{formats, RecordWriter};
+
+    /// The frames of a 3D smoke file on a 2x2x1 cell mesh (18 nodes), with
+    /// their encoded data.
+    fn frames() -> Vec<(f32, Vec<u8>, Vec<u8>)> {
+        let mut frame_2 = vec![0; 10];
+        frame_2.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        vec![
+            (0.0, vec![0; 18], vec![RLE_MARK, 0, 18]),
+            (0.5, frame_2, vec![RLE_MARK, 0, 10, 1, 2, 3, 4, 5, 6, 7, 8]),
+            (1.0, vec![254; 18], vec![RLE_MARK, 254, 18]),
+        ]
+    }
+
+    /// Build a 3D smoke file and its size file.
+    fn make_smoke3d_file(format: RecordFormat) -> (Vec<u8>, String) {
+        let mut out = RecordWriter::new(format);
+        let mut sizes = "0\n".to_string();
+        out.i32s(&[1, 0, 0, 2, 0, 2, 0, 1]);
+        for (time, values, encoded) in frames() {
+            out.f32s(&[time]);
+            out.i32s(&[values.len() as i32, encoded.len() as i32]);
+            out.bytes(&encoded);
+            sizes.push_str(&format!("{} {} {}\n", time, values.len(), encoded.len()));
+        }
+        (out.data, sizes)
+    }
+
+    #[test]
+    fn rle_decoding() {
+        assert_eq!(decode_rle(&[]), Vec::<u8>::new());
+        assert_eq!(decode_rle(&[3, RLE_MARK, 7, 3, 4]), vec![3, 7, 7, 7, 4]);
+        assert_eq!(decode_rle(&[RLE_MARK, RLE_MARK, 2]), vec![255, 255]);
+    }
+
+    #[test]
+    fn parse_smoke3d_simple() {
+        let (data, sizes) = make_smoke3d_file(RecordFormat::default());
+        let result = parse_smoke3d_file(&mut std::io::Cursor::new(&data)).unwrap();
+        assert_eq!(result.header.n_values(), 18);
+        assert_eq!(result.frames.len(), 3);
+        for (frame, (time, values, _)) in result.frames.iter().zip(frames()) {
+            assert_eq!(frame.time, time);
+            assert_eq!(frame.values, values);
+        }
+
+        let mut parser = Smoke3dParser::new(std::io::Cursor::new(&data)).unwrap();
+        for i in [2, 0, 1].iter() {
+            assert_eq!(parser.get_frame(*i).unwrap(), result.frames[*i]);
+        }
+
+        let sizes = parse_smoke3d_sizes(sizes.as_bytes()).unwrap();
+        assert_eq!(sizes.len(), 3);
+        assert_eq!(
+            sizes[1],
+            Smoke3dSize {
+                time: 0.5,
+                n_chars_in: 18,
+                n_chars_out: 11,
+            }
+        );
+        let mut parser = Smoke3dParser::with_sizes(std::io::Cursor::new(&data), &sizes).unwrap();
+        assert_eq!(
+            parser.seek_frame(2).unwrap(),
+            parser.frame_offsets.get(2).unwrap()
+        );
+        assert_eq!(parser.parse_frame().unwrap(), result.frames[2]);
+
+        let format = formats()[1];
+        let (data, _) = make_smoke3d_file(format);
+        let mut parser = Smoke3dParser::with_sizes(std::io::Cursor::new(&data), &sizes).unwrap();
+        assert_eq!(parser.format(), format);
+        assert_eq!(parser.header, result.header);
+        assert_eq!(parser.get_frame(2).unwrap(), result.frames[2]);
+        let mut parser = Smoke3dParser::new(std::io::Cursor::new(&data)).unwrap();
+        assert_eq!(parser.get_frame(1).unwrap(), result.frames[1]);
+    }
+
+    #[test]
+    fn parse_smoke3d_invalid_header() {
+        // A negative dimension, and an i_max less than i_min.
+        for header in [[1, 0, -1, 2, 0, 1, 0, 1], [1, 0, 5, 2, 0, 1, 0, 1]].iter() {
+            let mut data = RecordWriter::new(RecordFormat::default());
+            data.i32s(header);
+            let result = Smoke3dParser::new(std::io::Cursor::new(&data.data));
+            assert!(matches!(result, Err(ParseSmoke3dError::InvalidHeader)));
+        }
+    }
+}
@@ -2,7 +2,6 @@ use chid::{Chid, Title};
 use std::{
     convert::{TryFrom, TryInto},
     io::{BufRead, BufReader, Read},
-    ops::Deref,
     path::Path,
     str::FromStr,
     vec,
@@ -54,7 +53,7 @@ pub type GridCoord = i64;
 pub type Coord = f64;
 
 /// A sextuple of grid coordinates representing a region of cells.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct GridRegion {
     pub i1: GridCoord,
     pub i2: GridCoord,
@@ -130,7 +129,7 @@ impl Xyz {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct SmvFile {
     pub title: Title,
     pub chid: Chid,
@@ -150,20 +149,203 @@ pub struct SmvFile {
     pub events: Vec<SmvEvent>,
     pub device_acts: Vec<SmvDeviceAct>,
     pub slcfs: Vec<Slcf>,
+    pub smoke_3d: Vec<Smoke3d>,
+    pub isofs: Vec<Isof>,
+    pub pl3ds: Vec<Pl3d>,
     pub prt5s: Vec<Prt5>,
     pub bndfs: Vec<Bndf>,
     pub devcs: Vec<SmvDevice>,
     pub texture_origin: Option<Xyz>,
+    pub ramps: Vec<SmvRamp>,
+    pub props: Vec<SmvProp>,
+    /// The blocks which are not parsed, in the order they were read.
+    pub unparsed_blocks: Vec<UnparsedBlock>,
+}
+
+/// A block which is not parsed (e.g. OUTLINE or CVENT), kept as text so that
+/// it can be written back unchanged.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnparsedBlock {
+    /// The number of meshes before the block. Blocks such as CVENT belong to
+    /// the last mesh before them.
+    pub n_meshes_before: usize,
+    /// The lines of the block, starting with the line holding its name.
+    /// Blank lines are not kept.
+    pub lines: Vec<String>,
 }
 
 impl SmvFile {
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
-        let smv_file = std::fs::File::open(path)?;
-        let smv_data = parse_smv_file(smv_file)?;
-        Ok(smv_data)
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ParseSmvError> {
+        let smv_file = std::fs::File::open(path).map_err(ParseSmvError::IOError)?;
+        parse_smv_file(smv_file)
+    }
+
+    /// Find a RAMP by name.
+    pub fn ramp(&self, name: &str) -> Option<&SmvRamp> {
+        self.ramps.iter().find(|ramp| ramp.name == name)
+    }
+
+    /// The mesh a slice is in.
+    pub fn slice_mesh(&self, slcf: &Slcf) -> Option<&SmvMesh> {
+        self.meshes.get(slcf.mesh.checked_sub(1)?)
+    }
+
+    /// The physical bounds of a slice.
+    pub fn slice_xb(&self, slcf: &Slcf) -> Option<Xb> {
+        self.slice_mesh(slcf)?.xb_from_grid(slcf.ijk)
+    }
+
+    /// Find a DEVICE by name.
+    pub fn device(&self, name: &str) -> Option<&SmvDevice> {
+        self.devcs.iter().find(|devc| devc.name == name)
+    }
+
+    /// The PROP used by a device.
+    pub fn device_prop(&self, device: &SmvDevice) -> Option<&SmvProp> {
+        self.props.get(device.prop_index?)
+    }
+
+    /// All the devices drawn with the given Smokeview object, e.g.
+    /// "sprinkler_pendent" or "smoke_detector".
+    pub fn devices_with_smokeview_id<'a>(
+        &'a self,
+        smokeview_id: &'a str,
+    ) -> impl Iterator<Item = &'a SmvDevice> + 'a {
+        self.devcs.iter().filter(move |devc| {
+            self.device_prop(devc)
+                .map(|prop| prop.smokeview_ids.iter().any(|id| id == smokeview_id))
+                .unwrap_or(false)
+        })
+    }
+}
+
+/// An error encountered while parsing an SMV file.
+#[derive(Debug)]
+pub enum ParseSmvError {
+    IOError(std::io::Error),
+    /// A line within a block could not be parsed. `line` is 1-based.
+    Line {
+        block: String,
+        line: usize,
+        text: String,
+        cause: ParseLineError,
+    },
+    /// The file ended part way through a block.
+    UnexpectedEof {
+        block: String,
+        line: usize,
+    },
+    /// A block required to build an [`SmvFile`] was not present.
+    MissingBlock(&'static str),
+    /// The number of GRID, PDIM, OFFSET, TRN*, OBST and VENT blocks differ.
+    UnbalancedMeshes,
+}
+
+impl std::fmt::Display for ParseSmvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IOError(err) => write!(f, "could not read SMV file: {}", err),
+            Self::Line {
+                block,
+                line,
+                text,
+                cause,
+            } => write!(
+                f,
+                "line {} ({} block): {}: \"{}\"",
+                line,
+                block,
+                cause,
+                text.trim()
+            ),
+            Self::UnexpectedEof { block, line } => write!(
+                f,
+                "SMV file ended at line {} part way through a {} block",
+                line, block
+            ),
+            Self::MissingBlock(name) => write!(f, "SMV file has no {} block", name),
+            Self::UnbalancedMeshes => write!(
+                f,
+                "SMV file has an unequal number of GRID, PDIM, OFFSET, TRN, OBST and VENT blocks"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseSmvError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IOError(err) => Some(err),
+            Self::Line { cause, .. } => Some(cause),
+            _ => None,
+        }
     }
 }
 
+/// The reason a single line of an SMV file could not be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseLineError {
+    /// The line ended before all the expected values were read.
+    MissingValue,
+    /// The line was expected to be indented but was not.
+    MissingIndent,
+    InvalidInt(std::num::ParseIntError),
+    InvalidFloat(std::num::ParseFloatError),
+    InvalidChid(String),
+    InvalidTitle(String),
+    /// The values were read but are not consistent with each other.
+    InvalidValue(&'static str),
+}
+
+impl std::fmt::Display for ParseLineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingValue => write!(f, "expected another value"),
+            Self::MissingIndent => write!(f, "expected an indented line"),
+            Self::InvalidInt(err) => write!(f, "invalid integer: {}", err),
+            Self::InvalidFloat(err) => write!(f, "invalid number: {}", err),
+            Self::InvalidChid(err) => write!(f, "invalid CHID: {}", err),
+            Self::InvalidTitle(err) => write!(f, "invalid TITLE: {}", err),
+            Self::InvalidValue(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ParseLineError {}
+
+impl From<std::num::ParseIntError> for ParseLineError {
+    fn from(err: std::num::ParseIntError) -> Self {
+        Self::InvalidInt(err)
+    }
+}
+
+impl From<std::num::ParseFloatError> for ParseLineError {
+    fn from(err: std::num::ParseFloatError) -> Self {
+        Self::InvalidFloat(err)
+    }
+}
+
+impl From<std::convert::Infallible> for ParseLineError {
+    fn from(err: std::convert::Infallible) -> Self {
+        match err {}
+    }
+}
+
+/// Take the next whitespace separated value from `values` and parse it.
+fn next_value<'a, T, I>(values: &mut I) -> Result<T, ParseLineError>
+where
+    T: FromStr,
+    ParseLineError: From<T::Err>,
+    I: Iterator<Item = &'a str>,
+{
+    Ok(values.next().ok_or(ParseLineError::MissingValue)?.parse()?)
+}
+
+/// Remove the single space which indents the lines within a block.
+fn strip_indent(line: &str) -> Result<&str, ParseLineError> {
+    line.strip_prefix(' ').ok_or(ParseLineError::MissingIndent)
+}
+
 pub type SurfIndex = u64;
 
 /// The surface indices for each side of the obst.
@@ -214,6 +396,7 @@ pub struct SmvObst {
     pub xb_exact: Xb,
     pub id: i64,
     pub surfaces: Surfaces,
+    pub texture_origin: Option<Xyz>,
     pub ijk: GridRegion,
     pub colour_index: i64,
     pub block_type: i64,
@@ -225,6 +408,7 @@ impl SmvObst {
             xb_exact: half1.xb_exact,
             id: half1.blockage_id,
             surfaces: half1.surfaces,
+            texture_origin: half1.texture_origin,
             ijk: half2.ijk,
             colour_index: half2.color_index,
             block_type: half2.block_type,
@@ -242,6 +426,8 @@ pub struct SmvVent {
     pub vent_index: i64,
     pub vent_type: i64,
     pub color: Option<Rgbaf>,
+    /// Dummy vents are written by FDS to cover the exterior faces of a mesh.
+    pub dummy: bool,
 }
 
 impl SmvVent {
@@ -255,20 +441,21 @@ impl SmvVent {
             vent_index: half2.vent_index,
             vent_type: half2.vent_type,
             color: half2.color,
+            dummy: false,
         }
     }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
 pub struct ViewTimes {
-    tour_tstart: f64,
-    tour_tstop: f64,
-    tour_ntimes: usize,
+    pub(crate) tour_tstart: f64,
+    pub(crate) tour_tstop: f64,
+    pub(crate) tour_ntimes: usize,
 }
 
 #[derive(Clone, Debug, Default)]
 struct PendingSmvFile {
-    title: Option<String>,
+    title: Option<Title>,
     fds_version: Option<String>,
     revision: Option<String>,
     n_meshes: Option<u64>,
@@ -279,7 +466,7 @@ struct PendingSmvFile {
     albedo: Option<f64>,
     i_blank: Option<u64>,
     gvec: Option<Xyz>,
-    chid: Option<String>,
+    chid: Option<Chid>,
     csvfs: Vec<CSVEntry>,
     offsets: Vec<Xyz>,
     grids: Vec<GridBlock>,
@@ -301,6 +488,11 @@ struct PendingSmvFile {
     solid_ht3d: Option<i64>,
     texture_origin: Option<Xyz>,
     smoke_3d: Vec<Smoke3d>,
+    isofs: Vec<Isof>,
+    pl3ds: Vec<Pl3d>,
+    ramps: Vec<SmvRamp>,
+    props: Vec<SmvProp>,
+    unparsed_blocks: Vec<UnparsedBlock>,
 }
 
 impl PendingSmvFile {
@@ -314,29 +506,131 @@ impl PendingSmvFile {
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Slcf {
     pub cell_centred: bool,
-    pub vs: String,
+    /// The 1-based index of the mesh the slice is in.
+    pub mesh: usize,
+    pub slice_type: SliceType,
+    pub id: Option<String>,
+    /// The grid bounds of the slice within the mesh.
+    pub ijk: GridRegion,
+    /// The index of the slice as given on the SLCF line.
+    pub index: Option<u64>,
     pub filename: String,
     pub long_name: String,
     pub short_name: String,
     pub units: String,
 }
 
+impl Slcf {
+    /// The axis normal to the slice if it is a plane, i.e. if its extent is
+    /// zero along exactly one axis.
+    pub fn plane(&self) -> Option<Axis> {
+        let ijk = &self.ijk;
+        match (ijk.i1 == ijk.i2, ijk.j1 == ijk.j2, ijk.k1 == ijk.k2) {
+            (true, false, false) => Some(Axis::X),
+            (false, true, false) => Some(Axis::Y),
+            (false, false, true) => Some(Axis::Z),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SliceType {
+    Structured,
+    Unstructured,
+    Terrain,
+}
+
+impl SliceType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Structured => "STRUCTURED",
+            Self::Unstructured => "UNSTRUCTURED",
+            Self::Terrain => "TERRAIN",
+        }
+    }
+}
+
+impl FromStr for SliceType {
+    type Err = ParseLineError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "STRUCTURED" => Ok(Self::Structured),
+            "UNSTRUCTURED" => Ok(Self::Unstructured),
+            "TERRAIN" => Ok(Self::Terrain),
+            _ => Err(ParseLineError::InvalidValue("unknown slice type")),
+        }
+    }
+}
+
+/// The values on the first line of an SLCF block (after the block name), e.g.
+/// `1 # STRUCTURED &   226   226     0    18     0    25 !      1`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct SlcfHeader {
+    mesh: usize,
+    slice_type: SliceType,
+    id: Option<String>,
+    ijk: GridRegion,
+    index: Option<u64>,
+}
+
+impl FromStr for SlcfHeader {
+    type Err = ParseLineError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut values = s.split_whitespace();
+        let mesh = next_value(&mut values)?;
+        let mut slice_type = SliceType::Structured;
+        let mut id = None;
+        // The type and ID are optional and precede the grid bounds.
+        loop {
+            match values.next().ok_or(ParseLineError::MissingValue)? {
+                "#" => slice_type = next_value(&mut values)?,
+                "%" => id = Some(next_value(&mut values)?),
+                "&" => break,
+                _ => return Err(ParseLineError::InvalidValue("expected '#', '%' or '&'")),
+            }
+        }
+        let i1 = next_value(&mut values)?;
+        let i2 = next_value(&mut values)?;
+        let j1 = next_value(&mut values)?;
+        let j2 = next_value(&mut values)?;
+        let k1 = next_value(&mut values)?;
+        let k2 = next_value(&mut values)?;
+        let index = match values.next() {
+            Some("!") => Some(next_value(&mut values)?),
+            _ => None,
+        };
+        Ok(SlcfHeader {
+            mesh,
+            slice_type,
+            id,
+            ijk: GridRegion::new(i1, i2, j1, j2, k1, k2),
+            index,
+        })
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Prt5 {
-    n: usize,
-    filename: String,
-    a: i64,
-    b: i64,
+    /// The 1-based index of the mesh.
+    pub mesh: usize,
+    pub filename: String,
+    /// The 1-based indices of the particle classes written to the file, in
+    /// the order they appear in each frame.
+    pub classes: Vec<usize>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Bndf {
-    a: u64,
-    b: u64,
-    filename: String,
-    long_name: String,
-    short_name: String,
-    units: String,
+    /// The 1-based index of the mesh.
+    pub mesh: u64,
+    pub(crate) b: u64,
+    pub filename: String,
+    pub long_name: String,
+    pub short_name: String,
+    pub units: String,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -410,20 +704,28 @@ impl SmvMesh {
             offset,
         }
     }
-    pub fn xb_from_grid(&self, ijk: GridRegion) -> Xb {
-        Xb {
-            x1: self.trnx.get(ijk.i1 as usize).unwrap().f,
-            x2: self.trnx.get(ijk.i2 as usize).unwrap().f,
-            y1: self.trny.get(ijk.j1 as usize).unwrap().f,
-            y2: self.trny.get(ijk.j2 as usize).unwrap().f,
-            z1: self.trnz.get(ijk.k1 as usize).unwrap().f,
-            z2: self.trnz.get(ijk.k2 as usize).unwrap().f,
-        }
+    /// Convert grid coordinates to physical coordinates. Returns None if the
+    /// grid coordinates are outside the mesh.
+    pub fn xb_from_grid(&self, ijk: GridRegion) -> Option<Xb> {
+        let coord = |trn: &[TrnEntry], i: GridCoord| -> Option<Coord> {
+            if i < 0 {
+                return None;
+            }
+            trn.get(i as usize).map(|entry| entry.f)
+        };
+        Some(Xb {
+            x1: coord(&self.trnx, ijk.i1)?,
+            x2: coord(&self.trnx, ijk.i2)?,
+            y1: coord(&self.trny, ijk.j1)?,
+            y2: coord(&self.trny, ijk.j2)?,
+            z1: coord(&self.trnz, ijk.k1)?,
+            z2: coord(&self.trnz, ijk.k2)?,
+        })
     }
 }
 
 impl TryFrom<PendingSmvFile> for SmvFile {
-    type Error = &'static str;
+    type Error = ParseSmvError;
     fn try_from(pending: PendingSmvFile) -> Result<SmvFile, Self::Error> {
         let n_grids = pending.grids.len();
         let equal_n = [
@@ -439,7 +741,7 @@ impl TryFrom<PendingSmvFile> for SmvFile {
         .iter()
         .all(|&item| item == n_grids);
         if !equal_n {
-            return Err("mesh entries unbalanced");
+            return Err(ParseSmvError::UnbalancedMeshes);
         }
         let iter = pending
             .grids
@@ -456,15 +758,24 @@ impl TryFrom<PendingSmvFile> for SmvFile {
             let mesh = SmvMesh::new(grid, obsts, vents, trnx, trny, trnz, pdim, offset);
             meshes.push(mesh);
         }
+        let mut devcs = pending.devcs;
+        for devc in devcs.iter_mut() {
+            devc.prop_index = pending
+                .props
+                .iter()
+                .position(|prop| prop.name == devc.prop_id);
+        }
         Ok(SmvFile {
-            title: pending.title.unwrap().parse().unwrap(),
-            chid: pending.chid.unwrap().parse().unwrap(),
+            title: pending.title.ok_or(ParseSmvError::MissingBlock("TITLE"))?,
+            chid: pending.chid.ok_or(ParseSmvError::MissingBlock("CHID"))?,
             csvfs: pending.csvfs,
             surfs: pending.surfs,
             meshes,
             xyzs: pending.xyzs,
             solid_ht3d: pending.solid_ht3d,
-            input_filename: pending.input_filename.unwrap(),
+            input_filename: pending
+                .input_filename
+                .ok_or(ParseSmvError::MissingBlock("INPF"))?,
             endf_filename: pending.endf_filename,
             fds_version: pending.fds_version,
             surf_def: pending.surf_def,
@@ -475,10 +786,16 @@ impl TryFrom<PendingSmvFile> for SmvFile {
             events: pending.events,
             device_acts: pending.device_acts,
             slcfs: pending.slcfs,
+            smoke_3d: pending.smoke_3d,
+            isofs: pending.isofs,
+            pl3ds: pending.pl3ds,
             prt5s: pending.prt5s,
             bndfs: pending.bndfs,
-            devcs: pending.devcs,
+            devcs,
             texture_origin: pending.texture_origin,
+            ramps: pending.ramps,
+            props: pending.props,
+            unparsed_blocks: pending.unparsed_blocks,
         })
     }
 }
@@ -492,30 +809,30 @@ pub struct ObstFirstHalf {
 }
 
 impl FromStr for ObstFirstHalf {
-    type Err = ();
+    type Err = ParseLineError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut values = s.split_whitespace();
-        let x1: f64 = values.next().ok_or(())?.parse().unwrap();
-        let x2: f64 = values.next().ok_or(())?.parse().unwrap();
-        let y1: f64 = values.next().ok_or(())?.parse().unwrap();
-        let y2: f64 = values.next().ok_or(())?.parse().unwrap();
-        let z1: f64 = values.next().ok_or(())?.parse().unwrap();
-        let z2: f64 = values.next().ok_or(())?.parse().unwrap();
-        let blockage_id: i64 = values.next().ok_or(())?.parse().unwrap();
-        let s_min_x: u64 = values.next().ok_or(())?.parse().unwrap();
-        let s_max_x: u64 = values.next().ok_or(())?.parse().unwrap();
-        let s_min_y: u64 = values.next().ok_or(())?.parse().unwrap();
-        let s_max_y: u64 = values.next().ok_or(())?.parse().unwrap();
-        let s_min_z: u64 = values.next().ok_or(())?.parse().unwrap();
-        let s_max_z: u64 = values.next().ok_or(())?.parse().unwrap();
+        let x1: f64 = next_value(&mut values)?;
+        let x2: f64 = next_value(&mut values)?;
+        let y1: f64 = next_value(&mut values)?;
+        let y2: f64 = next_value(&mut values)?;
+        let z1: f64 = next_value(&mut values)?;
+        let z2: f64 = next_value(&mut values)?;
+        let blockage_id: i64 = next_value(&mut values)?;
+        let s_min_x: u64 = next_value(&mut values)?;
+        let s_max_x: u64 = next_value(&mut values)?;
+        let s_min_y: u64 = next_value(&mut values)?;
+        let s_max_y: u64 = next_value(&mut values)?;
+        let s_min_z: u64 = next_value(&mut values)?;
+        let s_max_z: u64 = next_value(&mut values)?;
         let texture_origin = if let Some(s) = values.next() {
             if s == "!" {
                 None
             } else {
-                let x: f64 = s.parse().unwrap();
-                let y: f64 = values.next().ok_or(())?.parse().unwrap();
-                let z: f64 = values.next().ok_or(())?.parse().unwrap();
+                let x: f64 = s.parse()?;
+                let y: f64 = next_value(&mut values)?;
+                let z: f64 = next_value(&mut values)?;
                 Some(Xyz::new(x, y, z))
             }
         } else {
@@ -545,18 +862,18 @@ pub struct ObstSecondHalf {
 }
 
 impl FromStr for ObstSecondHalf {
-    type Err = ();
+    type Err = ParseLineError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut values = s.split_whitespace();
-        let i1: i64 = values.next().ok_or(())?.parse().unwrap();
-        let i2: i64 = values.next().ok_or(())?.parse().unwrap();
-        let j1: i64 = values.next().ok_or(())?.parse().unwrap();
-        let j2: i64 = values.next().ok_or(())?.parse().unwrap();
-        let k1: i64 = values.next().ok_or(())?.parse().unwrap();
-        let k2: i64 = values.next().ok_or(())?.parse().unwrap();
-        let color_index: i64 = values.next().ok_or(())?.parse().unwrap();
-        let block_type: i64 = values.next().ok_or(())?.parse().unwrap();
+        let i1: i64 = next_value(&mut values)?;
+        let i2: i64 = next_value(&mut values)?;
+        let j1: i64 = next_value(&mut values)?;
+        let j2: i64 = next_value(&mut values)?;
+        let k1: i64 = next_value(&mut values)?;
+        let k2: i64 = next_value(&mut values)?;
+        let color_index: i64 = next_value(&mut values)?;
+        let block_type: i64 = next_value(&mut values)?;
         Ok(ObstSecondHalf {
             ijk: GridRegion::new(i1, i2, j1, j2, k1, k2),
             color_index,
@@ -574,22 +891,22 @@ pub struct VentFirstHalf {
 }
 
 impl FromStr for VentFirstHalf {
-    type Err = ();
+    type Err = ParseLineError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut values = s.split_whitespace();
-        let xmin: f64 = values.next().ok_or(())?.parse().unwrap();
-        let xmax: f64 = values.next().ok_or(())?.parse().unwrap();
-        let ymin: f64 = values.next().ok_or(())?.parse().unwrap();
-        let ymax: f64 = values.next().ok_or(())?.parse().unwrap();
-        let zmin: f64 = values.next().ok_or(())?.parse().unwrap();
-        let zmax: f64 = values.next().ok_or(())?.parse().unwrap();
-        let vent_id: u64 = values.next().ok_or(())?.parse().unwrap();
-        let s_num: u64 = values.next().ok_or(())?.parse().unwrap();
+        let xmin: f64 = next_value(&mut values)?;
+        let xmax: f64 = next_value(&mut values)?;
+        let ymin: f64 = next_value(&mut values)?;
+        let ymax: f64 = next_value(&mut values)?;
+        let zmin: f64 = next_value(&mut values)?;
+        let zmax: f64 = next_value(&mut values)?;
+        let vent_id: u64 = next_value(&mut values)?;
+        let s_num: u64 = next_value(&mut values)?;
         let texture_origin = if let Some(s) = values.next() {
-            let x: f64 = s.parse().unwrap();
-            let y: f64 = values.next().ok_or(())?.parse().unwrap();
-            let z: f64 = values.next().ok_or(())?.parse().unwrap();
+            let x: f64 = s.parse()?;
+            let y: f64 = next_value(&mut values)?;
+            let z: f64 = next_value(&mut values)?;
             Some(Xyz::new(x, y, z))
         } else {
             None
@@ -623,16 +940,16 @@ impl VentSecondHalf {
 }
 
 impl FromStr for VentSecondHalf {
-    type Err = ();
+    type Err = ParseLineError;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
         let mut values = input.split_whitespace();
-        let i1: i64 = values.next().ok_or(())?.parse().unwrap();
-        let i2: i64 = values.next().ok_or(())?.parse().unwrap();
-        let j1: i64 = values.next().ok_or(())?.parse().unwrap();
-        let j2: i64 = values.next().ok_or(())?.parse().unwrap();
-        let k1: i64 = values.next().ok_or(())?.parse().unwrap();
-        let k2: i64 = values.next().ok_or(())?.parse().unwrap();
+        let i1: i64 = next_value(&mut values)?;
+        let i2: i64 = next_value(&mut values)?;
+        let j1: i64 = next_value(&mut values)?;
+        let j2: i64 = next_value(&mut values)?;
+        let k1: i64 = next_value(&mut values)?;
+        let k2: i64 = next_value(&mut values)?;
         let ijk = GridRegion {
             i1,
             i2,
@@ -641,13 +958,13 @@ impl FromStr for VentSecondHalf {
             k1,
             k2,
         };
-        let vent_index: i64 = values.next().ok_or(())?.parse().unwrap();
-        let vent_type: i64 = values.next().ok_or(())?.parse().unwrap();
+        let vent_index: i64 = next_value(&mut values)?;
+        let vent_type: i64 = next_value(&mut values)?;
         let color = if let Some(s) = values.next() {
-            let r: f64 = s.parse().unwrap();
-            let g: f64 = values.next().ok_or(())?.parse().unwrap();
-            let b: f64 = values.next().ok_or(())?.parse().unwrap();
-            let a: f64 = values.next().ok_or(())?.parse().unwrap();
+            let r: f64 = s.parse()?;
+            let g: f64 = next_value(&mut values)?;
+            let b: f64 = next_value(&mut values)?;
+            let a: f64 = next_value(&mut values)?;
             Some(Rgbaf::new(r, g, b, a))
         } else {
             None
@@ -660,21 +977,23 @@ impl FromStr for VentSecondHalf {
 enum ParserState {
     None,
     TitleBlock,
-    FdsVersion1,
-    FdsVersion2,
+    FdsVersion,
     Revision,
     NMeshes,
     ViewTimes,
     Albedo,
     IBlank,
     GVec,
-    Material,
-    ClassOfParticles,
-    Outline,
     TOffset,
-    HrrPuvCut,
-    Ramp,
-    Prop,
+    Ramp1,
+    Ramp2(usize),
+    Ramp3(usize, String),
+    Ramp4(usize, String, usize, Vec<(f64, f64)>),
+    Prop1,
+    Prop2(String),
+    Prop3(String, usize, Vec<String>),
+    Prop4(String, Vec<String>),
+    Prop5(String, Vec<String>, usize, Vec<String>),
     Device1,
     Device2(String, String),
     Offset,
@@ -690,24 +1009,27 @@ enum ParserState {
         Vec<VentFirstHalf>,
         Vec<VentSecondHalf>,
     ),
-    CVent,
+    /// A block which is not parsed, with the lines read so far.
+    Unparsed(Vec<String>),
     Smoke3d1(Smoke3dType, u64),
     Smoke3d2(Smoke3dType, u64, String),
     Smoke3d3(Smoke3dType, u64, String, String),
     Smoke3d4(Smoke3dType, u64, String, String, String),
+    Isof1(IsoType, u64),
+    Isof2(IsoType, u64, String),
+    Isof3(IsoType, u64, String, String),
+    Isof4(IsoType, u64, String, String, String),
     Bndf1(u64, u64),
     Bndf2(u64, u64, String),
     Bndf3(u64, u64, String, String),
     Bndf4(u64, u64, String, String, String),
-    Bndf5(u64, u64, String, String, String, String),
-    Slcf1(bool, String),
-    Slcf2(bool, String, String),
-    Slcf3(bool, String, String, String),
-    Slcf4(bool, String, String, String, String),
-    Slcf5(bool, String, String, String, String, String),
+    Slcf1(bool, SlcfHeader),
+    Slcf2(bool, SlcfHeader, String),
+    Slcf3(bool, SlcfHeader, String, String),
+    Slcf4(bool, SlcfHeader, String, String, String),
     Prt51(usize),
     Prt52(usize, String),
-    Prt53(usize, String, i64),
+    Prt53(usize, String, usize, Vec<usize>),
     DeviceAct(String),
     ChidBlock,
     SolidHt3d,
@@ -726,7 +1048,10 @@ enum ParserState {
     Trn1(Axis),
     Trn2(Axis, usize, Vec<TrnEntry>),
     Xyz,
-    Pl3d,
+    Pl3d1(f64, u64),
+    Pl3d2(Pl3d),
+    Pl3d3(Pl3d, String),
+    Pl3d4(Pl3d, String, String),
     CloseVent(usize),
     OpenVent(usize),
     HideObst(usize),
@@ -740,12 +1065,12 @@ pub struct TrnEntry {
 }
 
 impl FromStr for TrnEntry {
-    type Err = ();
+    type Err = ParseLineError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut values = s.split_whitespace();
-        let i = values.next().ok_or(())?.parse().unwrap();
-        let f = values.next().ok_or(())?.parse().unwrap();
+        let i = next_value(&mut values)?;
+        let f = next_value(&mut values)?;
         Ok(TrnEntry { i, f })
     }
 }
@@ -767,22 +1092,72 @@ pub enum SmvEvent {
 
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 pub struct SmvDeviceAct {
-    name: String,
-    n: usize,
-    i: usize,
-    v: f64,
+    pub(crate) name: String,
+    pub(crate) n: usize,
+    pub(crate) i: usize,
+    pub(crate) v: f64,
 }
 
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 pub struct SmvDevice {
-    name: String,
-    quantity: String,
-    p1: Xyz,
-    p2: Xyz,
-    ps: Option<(Xyz, Xyz)>,
-    beam_type: String,
-    state0: i32,
-    nparams: i32,
+    pub(crate) name: String,
+    pub(crate) quantity: String,
+    pub(crate) p1: Xyz,
+    pub(crate) p2: Xyz,
+    pub(crate) ps: Option<(Xyz, Xyz)>,
+    pub(crate) prop_id: String,
+    /// The index of the PROP named by `prop_id`, resolved once the whole file
+    /// has been read.
+    pub(crate) prop_index: Option<usize>,
+    pub(crate) state0: i32,
+    pub(crate) nparams: i32,
+}
+
+impl SmvDevice {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn quantity(&self) -> &str {
+        &self.quantity
+    }
+    /// The location of the device.
+    pub fn xyz(&self) -> Xyz {
+        self.p1
+    }
+    /// The orientation vector of the device.
+    pub fn orientation(&self) -> Xyz {
+        self.p2
+    }
+    /// The two corners of the region over which the device applies, if it is
+    /// defined with an XB.
+    pub fn bounds(&self) -> Option<(Xyz, Xyz)> {
+        self.ps
+    }
+    /// The ID of the PROP used by the device. This is "null" if the device
+    /// has no PROP.
+    pub fn prop_id(&self) -> &str {
+        &self.prop_id
+    }
+    /// The index of the device's PROP in [`SmvFile::props`].
+    pub fn prop_index(&self) -> Option<usize> {
+        self.prop_index
+    }
+    pub fn initial_state(&self) -> i32 {
+        self.state0
+    }
+    pub fn n_params(&self) -> i32 {
+        self.nparams
+    }
+}
+
+/// A PROP, which defines the Smokeview object(s) used to draw a device.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SmvProp {
+    pub name: String,
+    /// The Smokeview object names, e.g. "sprinkler_pendent".
+    pub smokeview_ids: Vec<String>,
+    /// The Smokeview parameters as "NAME=VALUE" strings.
+    pub parameters: Vec<String>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -801,13 +1176,57 @@ pub struct Smoke3d {
     pub units: String,
 }
 
-pub fn parse_smv_file<R: Read>(input: R) -> Result<SmvFile, Box<dyn std::error::Error>> {
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IsoType {
+    /// An ISOF entry, for an isosurface on the structured grid.
+    Structured,
+    /// An ISOG entry, for an isosurface that includes complex geometry.
+    Geometry,
+}
+
+/// An isosurface file, written for each mesh for an ISOF namelist group.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Isof {
+    pub iso_type: IsoType,
+    pub mesh: u64,
+    pub filename: String,
+    pub long_name: String,
+    pub short_name: String,
+    pub units: String,
+}
+
+/// The number of quantities in each PLOT3D file.
+pub const PL3D_QUANTITIES: usize = 5;
+
+/// A PLOT3D solution file, written for each mesh at each PLOT3D output time.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct Pl3d {
+    pub time: f64,
+    pub mesh: u64,
+    pub filename: String,
+    /// The quantities in the file, in the order they are written.
+    pub quantities: Vec<Pl3dQuantity>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pl3dQuantity {
+    pub long_name: String,
+    pub short_name: String,
+    pub units: String,
+}
+
+pub fn parse_smv_file<R: Read>(input: R) -> Result<SmvFile, ParseSmvError> {
     let reader = BufReader::new(input);
     let lines = reader.lines();
     let mut state: ParserState = ParserState::None;
     let mut pending_file = PendingSmvFile::new();
-    for line in lines {
-        let line = line?;
+    // The name of the block we are currently in and the number of the last
+    // line read, for error reporting.
+    let mut block = String::new();
+    let mut line_number = 0;
+    for (i, line) in lines.enumerate() {
+        line_number = i + 1;
+        let line = line.map_err(ParseSmvError::IOError)?;
         if line.is_empty() {
             // Skip over blank lines
             continue;
@@ -815,729 +1234,930 @@ pub fn parse_smv_file<R: Read>(input: R) -> Result<SmvFile, Box<dyn std::error::
         let end_block = line.starts_with(|c: char| !c.is_whitespace());
         // Apply special end conditions
         if end_block {
-            match state {
-                ParserState::Trn2(axis, _skip_n, entries) => {
-                    let trns_base = match axis {
-                        Axis::X => &mut pending_file.trnx,
-                        Axis::Y => &mut pending_file.trny,
-                        Axis::Z => &mut pending_file.trnz,
-                    };
-                    trns_base.push(entries);
-                    state = ParserState::None;
+            state = close_block(state, &mut pending_file);
+            if let ParserState::None = state {
+                block = line.split_whitespace().next().unwrap_or("").to_string();
+            }
+        }
+        state =
+            parse_line(state, &line, &mut pending_file).map_err(|cause| ParseSmvError::Line {
+                block: block.clone(),
+                line: line_number,
+                text: line.clone(),
+                cause,
+            })?;
+    }
+    // Blocks without a fixed length are only finished by the start of the
+    // next block, so they may still be open. Any other open block means the
+    // file has been truncated.
+    match state {
+        ParserState::None => (),
+        ParserState::Trn2(..)
+        | ParserState::ObstBlock2(0, _)
+        | ParserState::Vent2(0, _, 0, _)
+        | ParserState::Unparsed(_) => {
+            close_block(state, &mut pending_file);
+        }
+        _ => {
+            return Err(ParseSmvError::UnexpectedEof {
+                block,
+                line: line_number,
+            })
+        }
+    }
+    pending_file.try_into()
+}
+
+/// Apply the end conditions of the current block when a new (unindented)
+/// line is reached, returning the state in which that line should be parsed.
+fn close_block(state: ParserState, pending_file: &mut PendingSmvFile) -> ParserState {
+    match state {
+        ParserState::Trn2(axis, _skip_n, entries) => {
+            let trns_base = match axis {
+                Axis::X => &mut pending_file.trnx,
+                Axis::Y => &mut pending_file.trny,
+                Axis::Z => &mut pending_file.trnz,
+            };
+            trns_base.push(entries);
+            ParserState::None
+        }
+        ParserState::ObstBlock2(0, _) => {
+            pending_file.obsts.push(vec![]);
+            ParserState::None
+        }
+        ParserState::Vent2(0, _, 0, _) => {
+            pending_file.vents.push(vec![]);
+            ParserState::None
+        }
+        ParserState::Unparsed(lines) => {
+            pending_file.unparsed_blocks.push(UnparsedBlock {
+                n_meshes_before: pending_file.grids.len(),
+                lines,
+            });
+            ParserState::None
+        }
+        ParserState::Surface3(_, _, _) => state,
+        // These blocks don't have spaces at the start
+        ParserState::FdsVersion | ParserState::Revision => state,
+        _ => ParserState::None,
+    }
+}
+
+/// The parser state after a ramp has been read, where `n` ramps (including
+/// that one) remained to be read in the RAMP block.
+fn next_ramp(n: usize) -> ParserState {
+    if n > 1 {
+        ParserState::Ramp2(n - 1)
+    } else {
+        ParserState::None
+    }
+}
+
+/// Parse a single non-empty line of an SMV file in the given parser state,
+/// returning the state for the next line.
+fn parse_line(
+    mut state: ParserState,
+    line: &str,
+    pending_file: &mut PendingSmvFile,
+) -> Result<ParserState, ParseLineError> {
+    match state {
+        ParserState::None => {
+            // We are not currently in a block. Therefore this line should
+            // contain the name of a block.
+            if line.starts_with(|c: char| c.is_whitespace()) {
+                return Ok(state);
+            }
+            let (name, remainder) = if let Some(n) = line.find(|c: char| c.is_whitespace()) {
+                line.split_at(n)
+            } else {
+                (line, "")
+            };
+            match name {
+                "TITLE" => {
+                    state = ParserState::TitleBlock;
                 }
-                ParserState::ObstBlock2(0, _) => {
-                    pending_file.obsts.push(vec![]);
-                    state = ParserState::None;
+                "FDSVERSION" => {
+                    state = ParserState::FdsVersion;
                 }
-                ParserState::Vent2(0, _, 0, _) => {
-                    pending_file.vents.push(vec![]);
-                    state = ParserState::None;
+                "REVISION" => {
+                    state = ParserState::Revision;
                 }
-                ParserState::Surface3(_, _, _) => (),
-                // These blocks don't have spaces at the start
-                ParserState::FdsVersion1 | ParserState::FdsVersion2 | ParserState::Revision => (),
-                _ => state = ParserState::None,
-            }
-        }
-        match state {
-            ParserState::None => {
-                // We are not currently in a block. Therefore this line should
-                // contain the name of a block.
-                if line.starts_with(|c: char| c.is_whitespace()) {
-                    continue;
+                "CHID" => {
+                    state = ParserState::ChidBlock;
                 }
-                let (name, remainder) = if let Some(n) = line.find(|c: char| c.is_whitespace()) {
-                    line.split_at(n)
-                } else {
-                    (line.deref(), "")
-                };
-                match name {
-                    "TITLE" => {
-                        state = ParserState::TitleBlock;
-                    }
-                    "FDSVERSION" => {
-                        state = ParserState::FdsVersion1;
-                    }
-                    "REVISION" => {
-                        state = ParserState::Revision;
-                    }
-                    "CHID" => {
-                        state = ParserState::ChidBlock;
-                    }
-                    "NMESHES" => {
-                        state = ParserState::NMeshes;
-                    }
-                    "VIEWTIMES" => {
-                        state = ParserState::ViewTimes;
-                    }
-                    "ALBEDO" => {
-                        state = ParserState::Albedo;
-                    }
-                    "IBLANK" => {
-                        state = ParserState::IBlank;
-                    }
-                    "GVEC" => {
-                        state = ParserState::GVec;
-                    }
-                    "MATERIAL" => {
-                        state = ParserState::Material;
-                    }
-                    "CLASS_OF_PARTICLES" => {
-                        state = ParserState::ClassOfParticles;
-                    }
-                    "OUTLINE" => {
-                        state = ParserState::Outline;
-                    }
-                    "TOFFSET" => {
-                        state = ParserState::TOffset;
-                    }
-                    "HRRPUVCUT" => {
-                        state = ParserState::HrrPuvCut;
-                    }
-                    "RAMP" => {
-                        state = ParserState::Ramp;
-                    }
-                    "ENDF" => {
-                        state = ParserState::Endf;
-                    }
-                    "SURFDEF" => {
-                        state = ParserState::SurfDef;
-                    }
-                    "PROP" => {
-                        state = ParserState::Prop;
-                    }
-                    "DEVICE" => {
-                        state = ParserState::Device1;
-                    }
-                    "OFFSET" => {
-                        state = ParserState::Offset;
-                    }
-                    "GRID" => {
-                        remainder.strip_prefix(' ').unwrap();
-                        state = ParserState::Grid(remainder.to_string());
-                    }
-                    "PDIM" => {
-                        state = ParserState::Pdim;
-                    }
-                    "VENT" => {
-                        state = ParserState::Vent1;
-                    }
-                    "CVENT" => {
-                        state = ParserState::CVent;
-                    }
-                    "SMOKF3D" => {
-                        // eprintln!(">{remainder}<");
-                        let n = remainder
-                            .trim()
-                            .split_ascii_whitespace()
-                            .next()
-                            .unwrap()
-                            .parse()
-                            .unwrap();
-                        state = ParserState::Smoke3d1(Smoke3dType::F, n);
-                    }
-                    "SMOKG3D" => {
-                        let n = remainder
-                            .trim()
-                            .split_ascii_whitespace()
-                            .next()
-                            .unwrap()
-                            .parse()
-                            .unwrap();
-                        state = ParserState::Smoke3d1(Smoke3dType::G, n);
-                    }
-                    "SLCC" => {
-                        state = ParserState::Slcf1(true, remainder.to_string());
-                    }
-                    "SLCF" => {
-                        state = ParserState::Slcf1(false, remainder.to_string());
-                    }
-                    "BNDF" => {
-                        let mut values = remainder.split_whitespace();
-                        let a = values.next().unwrap().parse().unwrap();
-                        let b = values.next().unwrap().parse().unwrap();
-                        state = ParserState::Bndf1(a, b);
-                    }
-                    "PRT5" => {
-                        let n = remainder.trim().parse().unwrap();
-                        state = ParserState::Prt51(n);
-                    }
-                    "DEVICE_ACT" => {
-                        state = ParserState::DeviceAct(remainder.to_string());
-                    }
-                    "CSVF" => {
-                        state = ParserState::CsvfBlock1;
-                    }
-                    "INPF" => {
-                        state = ParserState::InpfBlock;
-                    }
-                    "OBST" => {
-                        state = ParserState::ObstBlock1;
-                    }
-                    "TRNX" => {
-                        state = ParserState::Trn1(Axis::X);
-                    }
-                    "TRNY" => {
-                        state = ParserState::Trn1(Axis::Y);
-                    }
-                    "TRNZ" => {
-                        state = ParserState::Trn1(Axis::Z);
-                    }
-                    "SURFACE" => {
-                        state = ParserState::Surface1;
-                    }
-                    "SOLID_HT3D" => {
-                        state = ParserState::SolidHt3d;
-                    }
-                    "PL3D" => {
-                        state = ParserState::Pl3d;
-                    }
-                    "XYZ" => {
-                        state = ParserState::Xyz;
-                    }
-                    "CLOSE_VENT" => {
-                        let n = remainder.trim().parse().unwrap();
-                        state = ParserState::CloseVent(n);
-                    }
-                    "OPEN_VENT" => {
-                        let n = remainder.trim().parse().unwrap();
-                        state = ParserState::OpenVent(n);
-                    }
-                    "HIDE_OBST" => {
-                        let n = remainder.trim().parse().unwrap();
-                        state = ParserState::HideObst(n);
-                    }
-                    "SHOW_OBST" => {
-                        let n = remainder.trim().parse().unwrap();
-                        state = ParserState::ShowObst(n);
-                    }
-                    name => {
-                        eprintln!("Unrecognized block: \"{}\"", name);
-                        state = ParserState::None;
-                    }
+                "NMESHES" => {
+                    state = ParserState::NMeshes;
                 }
-            }
-            ParserState::TitleBlock => {
-                // This line is the title
-                let line = line.strip_prefix(' ').unwrap();
-                pending_file.title = Some(line.parse()?);
-                state = ParserState::None;
-            }
-            ParserState::FdsVersion1 => {
-                // This line is the title
-                pending_file.fds_version = Some(line.parse()?);
-                state = ParserState::FdsVersion2;
-            }
-            ParserState::FdsVersion2 => {
-                // This line is the title
-                pending_file.fds_version = Some(line.parse()?);
-                state = ParserState::None;
-            }
-            ParserState::Revision => {
-                // This line is the title
-                pending_file.revision = Some(line.parse()?);
-                state = ParserState::None;
-            }
-            ParserState::NMeshes => {
-                let line = line.strip_prefix(' ').unwrap();
-                pending_file.n_meshes = Some(line.trim().parse()?);
-                state = ParserState::None;
-            }
-            ParserState::ViewTimes => {
-                let line = line.strip_prefix(' ').unwrap();
-                let line = line.trim();
-                let mut values = line.split_whitespace();
-                let tour_tstart: f64 = values.next().unwrap().parse().unwrap();
-                let tour_tstop: f64 = values.next().unwrap().parse().unwrap();
-                let tour_ntimes: usize = values.next().unwrap().parse().unwrap();
-                pending_file.view_times = Some(ViewTimes {
-                    tour_tstart,
-                    tour_tstop,
-                    tour_ntimes,
-                });
-                state = ParserState::None;
-            }
-            ParserState::Albedo => {
-                let line = line.strip_prefix(' ').unwrap();
-                let albedo: f64 = line.trim().parse()?;
-                pending_file.albedo = Some(albedo);
-                state = ParserState::None;
-            }
-            ParserState::IBlank => {
-                let line = line.strip_prefix(' ').unwrap();
-                let i_blank: u64 = line.trim().parse()?;
-                pending_file.i_blank = Some(i_blank);
-                state = ParserState::None;
-            }
-            ParserState::GVec => {
-                let line = line.strip_prefix(' ').unwrap();
-                let mut values = line.split_whitespace();
-                let x: f64 = values.next().unwrap().parse().unwrap();
-                let y: f64 = values.next().unwrap().parse().unwrap();
-                let z: f64 = values.next().unwrap().parse().unwrap();
-                pending_file.gvec = Some(Xyz { x, y, z });
-                state = ParserState::None;
-            }
-            ParserState::Material => {
-                // TODO: Parse material
-                state = ParserState::None;
-            }
-            ParserState::ClassOfParticles => {
-                // TODO: Parse material
-                state = ParserState::None;
-            }
-            ParserState::Outline => {
-                // TODO: Parse outline
-                state = ParserState::None;
-            }
-            ParserState::Pl3d => {
-                // TODO: Parse
-                state = ParserState::None;
-            }
-            ParserState::CloseVent(n) => {
-                let mut values = line.trim().split_whitespace();
-                let i = values.next().unwrap().parse().unwrap();
-                let time = values.next().unwrap().parse().unwrap();
-                pending_file
-                    .events
-                    .push(SmvEvent::CloseVent { n, i, t: time });
-                state = ParserState::None;
-            }
-            ParserState::OpenVent(n) => {
-                let mut values = line.trim().split_whitespace();
-                let i = values.next().unwrap().parse().unwrap();
-                let time: f64 = values.next().unwrap().parse().unwrap();
-                pending_file
-                    .events
-                    .push(SmvEvent::OpenVent { n, i, t: time });
-                state = ParserState::None;
-            }
-            ParserState::HideObst(n) => {
-                let mut values = line.trim().split_whitespace();
-                let i = values.next().unwrap().parse().unwrap();
-                let time: f64 = values.next().unwrap().parse().unwrap();
-                pending_file
-                    .events
-                    .push(SmvEvent::HideObst { n, i, t: time });
-                state = ParserState::None;
-            }
-            ParserState::ShowObst(n) => {
-                let mut values = line.trim().split_whitespace();
-                let i = values.next().unwrap().parse().unwrap();
-                let time: f64 = values.next().unwrap().parse().unwrap();
-                pending_file
-                    .events
-                    .push(SmvEvent::ShowObst { n, i, t: time });
-                state = ParserState::None;
-            }
-            ParserState::TOffset => {
-                let line = line.trim();
-                let mut values = line.split_whitespace();
-                let x = values.next().unwrap().parse().unwrap();
-                let y = values.next().unwrap().parse().unwrap();
-                let z = values.next().unwrap().parse().unwrap();
-                pending_file.texture_origin = Some(Xyz { x, y, z });
-                state = ParserState::None;
-            }
-            ParserState::HrrPuvCut => {
-                // TODO: Parse
-                state = ParserState::None;
-            }
-            ParserState::Ramp => {
-                // TODO: Parse
-                state = ParserState::None;
-            }
-            ParserState::Prop => {
-                // TODO: Parse
-                state = ParserState::None;
-            }
-            ParserState::Device1 => {
-                let line = line.trim();
-                let mut values = line.split('%');
-                let name = values.next().unwrap().parse().unwrap();
-                let quantity = values.next().unwrap().parse().unwrap();
-                state = ParserState::Device2(name, quantity);
-            }
-            ParserState::Device2(name, quantity) => {
-                let mut values = line.split_whitespace();
-                let x1 = values.next().unwrap().parse().unwrap();
-                let y1 = values.next().unwrap().parse().unwrap();
-                let z1 = values.next().unwrap().parse().unwrap();
-                let x2 = values.next().unwrap().parse().unwrap();
-                let y2 = values.next().unwrap().parse().unwrap();
-                let z2 = values.next().unwrap().parse().unwrap();
-                let state0: i32 = values.next().unwrap().parse().unwrap();
-                let nparams: i32 = values.next().unwrap().parse().unwrap();
-                let separator = values.next().unwrap();
-                let ps = if separator == "#" {
-                    let x1n = values.next().unwrap().parse().unwrap();
-                    let y1n = values.next().unwrap().parse().unwrap();
-                    let z1n = values.next().unwrap().parse().unwrap();
-                    let x2n = values.next().unwrap().parse().unwrap();
-                    let y2n = values.next().unwrap().parse().unwrap();
-                    let z2n = values.next().unwrap().parse().unwrap();
-                    let _extra_separator = values.next().unwrap();
-                    Some((Xyz::new(x1n, y1n, z1n), Xyz::new(x2n, y2n, z2n)))
-                } else {
-                    None
-                };
-                let beam_type = values.next().unwrap().parse().unwrap();
-
-                let device = SmvDevice {
-                    name,
-                    quantity,
-                    p1: Xyz::new(x1, y1, z1),
-                    p2: Xyz::new(x2, y2, z2),
-                    ps,
-                    beam_type,
-                    state0,
-                    nparams,
-                };
-                pending_file.devcs.push(device);
-                state = ParserState::None;
-            }
-
-            //             DEVICE
-            //  AOVVFlow % VOLUME FLOW
-            //     31.80000    13.00000    13.80000     0.00000     0.00000    -1.00000  0  0 #     31.30000    12.50000    13.80000    32.30000    13.50000    13.80000 % null
-            ParserState::Offset => {
-                let line = line.trim();
-                let mut values = line.split_whitespace();
-                let x = values.next().unwrap().parse().unwrap();
-                let y = values.next().unwrap().parse().unwrap();
-                let z = values.next().unwrap().parse().unwrap();
-                pending_file.offsets.push(Xyz { x, y, z });
-                state = ParserState::None;
-            }
-            ParserState::Pdim => {
-                let line = line.trim();
-                let mut values = line.split_whitespace();
-                let xbar0 = values.next().unwrap().parse().unwrap();
-                let xbar = values.next().unwrap().parse().unwrap();
-                let ybar0 = values.next().unwrap().parse().unwrap();
-                let ybar = values.next().unwrap().parse().unwrap();
-                let zbar0 = values.next().unwrap().parse().unwrap();
-                let zbar = values.next().unwrap().parse().unwrap();
-                let r = values.next().unwrap().parse().unwrap();
-                let g = values.next().unwrap().parse().unwrap();
-                let b = values.next().unwrap().parse().unwrap();
-                pending_file.pdims.push(PdimBlock {
-                    xbar0,
-                    xbar,
-                    ybar0,
-                    ybar,
-                    zbar0,
-                    zbar,
-                    color: Rgbf { r, g, b },
-                });
-                state = ParserState::None;
-            }
-            ParserState::Vent1 => {
-                let mut values = line.split_whitespace();
-                let total_vents: usize = values.next().unwrap().parse().unwrap();
-                let n_dummy_vents: usize = values.next().unwrap().parse().unwrap();
-                let n_vents = total_vents - n_dummy_vents;
-                let first_vents = Vec::with_capacity(n_vents);
-                let first_dummy_vents = Vec::with_capacity(n_dummy_vents);
-                state = ParserState::Vent2(n_vents, first_vents, n_dummy_vents, first_dummy_vents);
-            }
-            ParserState::Vent2(n_vents, mut first_vents, n_dummy_vents, mut first_dummy_vents) => {
-                let f = line.trim().parse().unwrap();
-                if first_vents.len() < n_vents {
-                    first_vents.push(f);
-                } else if first_dummy_vents.len() < n_dummy_vents {
-                    first_dummy_vents.push(f);
+                "VIEWTIMES" => {
+                    state = ParserState::ViewTimes;
                 }
-                if (first_vents.len() < n_vents) || first_dummy_vents.len() < n_dummy_vents {
-                    state =
-                        ParserState::Vent2(n_vents, first_vents, n_dummy_vents, first_dummy_vents);
-                } else {
-                    let second_vents = Vec::with_capacity(n_vents);
-                    let second_dummy_vents = Vec::with_capacity(n_dummy_vents);
-                    state = ParserState::Vent3(
-                        n_vents,
-                        first_vents,
-                        second_vents,
-                        n_dummy_vents,
-                        first_dummy_vents,
-                        second_dummy_vents,
-                    );
+                "ALBEDO" => {
+                    state = ParserState::Albedo;
                 }
-            }
-            ParserState::Vent3(
-                n_vents,
-                mut first_vents,
-                mut second_vents,
-                n_dummy_vents,
-                mut first_dummy_vents,
-                mut second_dummy_vents,
-            ) => {
-                let f = line.trim().parse().unwrap();
-                if second_vents.len() < n_vents {
-                    second_vents.push(f);
-                } else if second_dummy_vents.len() < n_dummy_vents {
-                    second_dummy_vents.push(f);
+                "IBLANK" => {
+                    state = ParserState::IBlank;
+                }
+                "GVEC" => {
+                    state = ParserState::GVec;
+                }
+                "TOFFSET" => {
+                    state = ParserState::TOffset;
+                }
+                "RAMP" => {
+                    state = ParserState::Ramp1;
+                }
+                "ENDF" => {
+                    state = ParserState::Endf;
+                }
+                "SURFDEF" => {
+                    state = ParserState::SurfDef;
+                }
+                "PROP" => {
+                    state = ParserState::Prop1;
+                }
+                "DEVICE" => {
+                    state = ParserState::Device1;
+                }
+                "OFFSET" => {
+                    state = ParserState::Offset;
+                }
+                "GRID" => {
+                    let name = strip_indent(remainder)?.trim();
+                    state = ParserState::Grid(name.to_string());
+                }
+                "PDIM" => {
+                    state = ParserState::Pdim;
+                }
+                "VENT" => {
+                    state = ParserState::Vent1;
                 }
-                if (second_vents.len() < n_vents) || second_dummy_vents.len() < n_dummy_vents {
-                    state = ParserState::Vent3(
-                        n_vents,
-                        first_vents,
-                        second_vents,
-                        n_dummy_vents,
-                        first_dummy_vents,
-                        second_dummy_vents,
-                    );
-                } else {
-                    // TODO: should normal and dummy be saved together? Currently they are.
-                    first_vents.append(&mut first_dummy_vents);
-                    second_vents.append(&mut second_dummy_vents);
-                    let mut vents = Vec::with_capacity(n_vents + n_dummy_vents);
-                    for (first, second) in first_vents.into_iter().zip(second_vents.into_iter()) {
-                        vents.push(SmvVent::new(first, second));
-                    }
-                    pending_file.vents.push(vents);
-                    state = ParserState::None;
+                "SMOKF3D" => {
+                    let n = next_value(&mut remainder.split_ascii_whitespace())?;
+                    state = ParserState::Smoke3d1(Smoke3dType::F, n);
+                }
+                "SMOKG3D" => {
+                    let n = next_value(&mut remainder.split_ascii_whitespace())?;
+                    state = ParserState::Smoke3d1(Smoke3dType::G, n);
+                }
+                "ISOF" => {
+                    let n = next_value(&mut remainder.split_ascii_whitespace())?;
+                    state = ParserState::Isof1(IsoType::Structured, n);
+                }
+                "ISOG" => {
+                    let n = next_value(&mut remainder.split_ascii_whitespace())?;
+                    state = ParserState::Isof1(IsoType::Geometry, n);
+                }
+                "SLCC" => {
+                    state = ParserState::Slcf1(true, remainder.parse()?);
+                }
+                "SLCF" => {
+                    state = ParserState::Slcf1(false, remainder.parse()?);
+                }
+                "SLCT" => {
+                    let mut header: SlcfHeader = remainder.parse()?;
+                    header.slice_type = SliceType::Terrain;
+                    state = ParserState::Slcf1(false, header);
+                }
+                "BNDF" => {
+                    let mut values = remainder.split_whitespace();
+                    let a = next_value(&mut values)?;
+                    let b = next_value(&mut values)?;
+                    state = ParserState::Bndf1(a, b);
+                }
+                "PRT5" => {
+                    let n = remainder.trim().parse()?;
+                    state = ParserState::Prt51(n);
+                }
+                "DEVICE_ACT" => {
+                    state = ParserState::DeviceAct(remainder.trim().to_string());
+                }
+                "CSVF" => {
+                    state = ParserState::CsvfBlock1;
+                }
+                "INPF" => {
+                    state = ParserState::InpfBlock;
+                }
+                "OBST" => {
+                    state = ParserState::ObstBlock1;
+                }
+                "TRNX" => {
+                    state = ParserState::Trn1(Axis::X);
+                }
+                "TRNY" => {
+                    state = ParserState::Trn1(Axis::Y);
+                }
+                "TRNZ" => {
+                    state = ParserState::Trn1(Axis::Z);
+                }
+                "SURFACE" => {
+                    state = ParserState::Surface1;
+                }
+                "SOLID_HT3D" => {
+                    state = ParserState::SolidHt3d;
+                }
+                "PL3D" => {
+                    let mut values = remainder.split_whitespace();
+                    let time = next_value(&mut values)?;
+                    let mesh = next_value(&mut values)?;
+                    state = ParserState::Pl3d1(time, mesh);
+                }
+                "XYZ" => {
+                    state = ParserState::Xyz;
+                }
+                "CLOSE_VENT" => {
+                    let n = remainder.trim().parse()?;
+                    state = ParserState::CloseVent(n);
+                }
+                "OPEN_VENT" => {
+                    let n = remainder.trim().parse()?;
+                    state = ParserState::OpenVent(n);
+                }
+                "HIDE_OBST" => {
+                    let n = remainder.trim().parse()?;
+                    state = ParserState::HideObst(n);
+                }
+                "SHOW_OBST" => {
+                    let n = remainder.trim().parse()?;
+                    state = ParserState::ShowObst(n);
+                }
+                // Blocks such as MATERIAL, OUTLINE and CVENT are kept as
+                // text.
+                _ => {
+                    state = ParserState::Unparsed(vec![line.to_string()]);
                 }
             }
-            ParserState::CVent => {
-                // TODO: Parse
+        }
+        ParserState::TitleBlock => {
+            // This line is the title
+            let line = strip_indent(line)?;
+            let title = line
+                .parse()
+                .map_err(|err| ParseLineError::InvalidTitle(format!("{:?}", err)))?;
+            pending_file.title = Some(title);
+            state = ParserState::None;
+        }
+        ParserState::FdsVersion => {
+            // This line is the FDS version
+            pending_file.fds_version = Some(line.to_string());
+            state = ParserState::None;
+        }
+        ParserState::Revision => {
+            // This line is the title
+            pending_file.revision = Some(line.parse()?);
+            state = ParserState::None;
+        }
+        ParserState::NMeshes => {
+            let line = strip_indent(line)?;
+            pending_file.n_meshes = Some(line.trim().parse()?);
+            state = ParserState::None;
+        }
+        ParserState::ViewTimes => {
+            let line = strip_indent(line)?;
+            let line = line.trim();
+            let mut values = line.split_whitespace();
+            let tour_tstart: f64 = next_value(&mut values)?;
+            let tour_tstop: f64 = next_value(&mut values)?;
+            let tour_ntimes: usize = next_value(&mut values)?;
+            pending_file.view_times = Some(ViewTimes {
+                tour_tstart,
+                tour_tstop,
+                tour_ntimes,
+            });
+            state = ParserState::None;
+        }
+        ParserState::Albedo => {
+            let line = strip_indent(line)?;
+            let albedo: f64 = line.trim().parse()?;
+            pending_file.albedo = Some(albedo);
+            state = ParserState::None;
+        }
+        ParserState::IBlank => {
+            let line = strip_indent(line)?;
+            let i_blank: u64 = line.trim().parse()?;
+            pending_file.i_blank = Some(i_blank);
+            state = ParserState::None;
+        }
+        ParserState::GVec => {
+            let line = strip_indent(line)?;
+            let mut values = line.split_whitespace();
+            let x: f64 = next_value(&mut values)?;
+            let y: f64 = next_value(&mut values)?;
+            let z: f64 = next_value(&mut values)?;
+            pending_file.gvec = Some(Xyz { x, y, z });
+            state = ParserState::None;
+        }
+        ParserState::Unparsed(ref mut lines) => {
+            lines.push(line.to_string());
+        }
+        ParserState::Pl3d1(time, mesh) => {
+            let filename = strip_indent(line)?.trim().to_string();
+            let pl3d = Pl3d {
+                time,
+                mesh,
+                filename,
+                quantities: Vec::with_capacity(PL3D_QUANTITIES),
+            };
+            state = ParserState::Pl3d2(pl3d);
+        }
+        ParserState::Pl3d2(pl3d) => {
+            let long_name = strip_indent(line)?.trim().to_string();
+            state = ParserState::Pl3d3(pl3d, long_name);
+        }
+        ParserState::Pl3d3(pl3d, long_name) => {
+            let short_name = strip_indent(line)?.trim().to_string();
+            state = ParserState::Pl3d4(pl3d, long_name, short_name);
+        }
+        ParserState::Pl3d4(mut pl3d, long_name, short_name) => {
+            let units = strip_indent(line)?.trim().to_string();
+            pl3d.quantities.push(Pl3dQuantity {
+                long_name,
+                short_name,
+                units,
+            });
+            if pl3d.quantities.len() >= PL3D_QUANTITIES {
+                pending_file.pl3ds.push(pl3d);
                 state = ParserState::None;
+            } else {
+                state = ParserState::Pl3d2(pl3d);
             }
-            ParserState::Grid(name) => {
-                let line = line.trim();
-                let mut values = line.split_whitespace();
-                let i_bar = values.next().unwrap().parse().unwrap();
-                let j_bar = values.next().unwrap().parse().unwrap();
-                let k_bar = values.next().unwrap().parse().unwrap();
-                let mesh_type = values.next().unwrap().parse().unwrap();
-                pending_file.grids.push(GridBlock {
+        }
+        ParserState::CloseVent(n) => {
+            let mut values = line.trim().split_whitespace();
+            let i = next_value(&mut values)?;
+            let time = next_value(&mut values)?;
+            pending_file
+                .events
+                .push(SmvEvent::CloseVent { n, i, t: time });
+            state = ParserState::None;
+        }
+        ParserState::OpenVent(n) => {
+            let mut values = line.trim().split_whitespace();
+            let i = next_value(&mut values)?;
+            let time: f64 = next_value(&mut values)?;
+            pending_file
+                .events
+                .push(SmvEvent::OpenVent { n, i, t: time });
+            state = ParserState::None;
+        }
+        ParserState::HideObst(n) => {
+            let mut values = line.trim().split_whitespace();
+            let i = next_value(&mut values)?;
+            let time: f64 = next_value(&mut values)?;
+            pending_file
+                .events
+                .push(SmvEvent::HideObst { n, i, t: time });
+            state = ParserState::None;
+        }
+        ParserState::ShowObst(n) => {
+            let mut values = line.trim().split_whitespace();
+            let i = next_value(&mut values)?;
+            let time: f64 = next_value(&mut values)?;
+            pending_file
+                .events
+                .push(SmvEvent::ShowObst { n, i, t: time });
+            state = ParserState::None;
+        }
+        ParserState::TOffset => {
+            let line = line.trim();
+            let mut values = line.split_whitespace();
+            let x = next_value(&mut values)?;
+            let y = next_value(&mut values)?;
+            let z = next_value(&mut values)?;
+            pending_file.texture_origin = Some(Xyz { x, y, z });
+            state = ParserState::None;
+        }
+        ParserState::Ramp1 => {
+            // This is the number of ramps
+            let n: usize = line.trim().parse()?;
+            state = if n > 0 {
+                ParserState::Ramp2(n)
+            } else {
+                ParserState::None
+            };
+        }
+        ParserState::Ramp2(n) => {
+            let name = line
+                .trim()
+                .strip_prefix("RAMP:")
+                .ok_or(ParseLineError::InvalidValue("expected \"RAMP: <name>\""))?;
+            state = ParserState::Ramp3(n, name.trim().to_string());
+        }
+        ParserState::Ramp3(n, name) => {
+            // This is the number of points in the ramp
+            let n_points: usize = line.trim().parse()?;
+            if n_points > 0 {
+                let points = Vec::with_capacity(n_points);
+                state = ParserState::Ramp4(n, name, n_points, points);
+            } else {
+                pending_file.ramps.push(SmvRamp {
                     name,
-                    i_bar,
-                    j_bar,
-                    k_bar,
-                    mesh_type,
+                    points: vec![],
                 });
-                state = ParserState::None;
+                state = next_ramp(n);
             }
-            ParserState::Smoke3d1(smoke_type, mesh) => {
-                let file_name = line.strip_prefix(' ').unwrap().trim().to_string();
-                state = ParserState::Smoke3d2(smoke_type, mesh, file_name);
-            }
-            ParserState::Smoke3d2(smoke_type, mesh, file_name) => {
-                let long_name = line.strip_prefix(' ').unwrap().trim().to_string();
-                state = ParserState::Smoke3d3(smoke_type, mesh, file_name, long_name);
-            }
-            ParserState::Smoke3d3(smoke_type, mesh, file_name, long_name) => {
-                let short_name = line.strip_prefix(' ').unwrap().trim().to_string();
-                state = ParserState::Smoke3d4(smoke_type, mesh, file_name, long_name, short_name);
+        }
+        ParserState::Ramp4(n, name, n_points, mut points) => {
+            let mut values = line.split_whitespace();
+            let t = next_value(&mut values)?;
+            let v = next_value(&mut values)?;
+            points.push((t, v));
+            if points.len() >= n_points {
+                pending_file.ramps.push(SmvRamp { name, points });
+                state = next_ramp(n);
+            } else {
+                state = ParserState::Ramp4(n, name, n_points, points);
             }
-            ParserState::Smoke3d4(smoke_type, mesh, file_name, long_name, short_name) => {
-                let units = line.strip_prefix(' ').unwrap().trim().to_string();
-                pending_file.smoke_3d.push(Smoke3d {
-                    smoke_type,
-                    file_name,
-                    mesh,
-                    long_name,
-                    short_name,
-                    units,
+        }
+        ParserState::Prop1 => {
+            let line = strip_indent(line)?;
+            state = ParserState::Prop2(line.trim().to_string());
+        }
+        ParserState::Prop2(name) => {
+            // This is the number of Smokeview IDs
+            let n: usize = line.trim().parse()?;
+            let smokeview_ids = Vec::with_capacity(n);
+            state = if n > 0 {
+                ParserState::Prop3(name, n, smokeview_ids)
+            } else {
+                ParserState::Prop4(name, smokeview_ids)
+            };
+        }
+        ParserState::Prop3(name, n, mut smokeview_ids) => {
+            let line = strip_indent(line)?;
+            smokeview_ids.push(line.trim().to_string());
+            state = if smokeview_ids.len() >= n {
+                ParserState::Prop4(name, smokeview_ids)
+            } else {
+                ParserState::Prop3(name, n, smokeview_ids)
+            };
+        }
+        ParserState::Prop4(name, smokeview_ids) => {
+            // This is the number of Smokeview parameters
+            let n: usize = line.trim().parse()?;
+            if n > 0 {
+                let parameters = Vec::with_capacity(n);
+                state = ParserState::Prop5(name, smokeview_ids, n, parameters);
+            } else {
+                pending_file.props.push(SmvProp {
+                    name,
+                    smokeview_ids,
+                    parameters: vec![],
                 });
                 state = ParserState::None;
             }
-            ParserState::Slcf1(cell_centred, vs) => {
-                let line = line.strip_prefix(' ').unwrap();
-                let filename = line.trim().to_string();
-                state = ParserState::Slcf2(cell_centred, vs, filename);
-            }
-            ParserState::Slcf2(cell_centred, vs, filename) => {
-                let line = line.strip_prefix(' ').unwrap();
-                let long_name = line.trim().to_string();
-                state = ParserState::Slcf3(cell_centred, vs, filename, long_name);
-            }
-            ParserState::Slcf3(cell_centred, vs, filename, long_name) => {
-                let line = line.strip_prefix(' ').unwrap();
-                let short_name = line.trim().to_string();
-                state = ParserState::Slcf4(cell_centred, vs, filename, long_name, short_name);
-            }
-            ParserState::Slcf4(cell_centred, vs, filename, long_name, short_name) => {
-                let line = line.strip_prefix(' ').unwrap();
-                let units = line.trim().to_string();
-                state =
-                    ParserState::Slcf5(cell_centred, vs, filename, long_name, short_name, units);
-            }
-            ParserState::Slcf5(cell_centred, vs, filename, long_name, short_name, units) => {
-                pending_file.slcfs.push(Slcf {
-                    cell_centred,
-                    vs,
-                    filename,
-                    long_name,
-                    short_name,
-                    units,
+        }
+        ParserState::Prop5(name, smokeview_ids, n, mut parameters) => {
+            let line = strip_indent(line)?;
+            parameters.push(line.trim().to_string());
+            if parameters.len() >= n {
+                pending_file.props.push(SmvProp {
+                    name,
+                    smokeview_ids,
+                    parameters,
                 });
                 state = ParserState::None;
+            } else {
+                state = ParserState::Prop5(name, smokeview_ids, n, parameters);
             }
-            ParserState::Bndf1(a, b) => {
-                let line = line.strip_prefix(' ').unwrap();
-                let filename = line.trim().to_string();
-                state = ParserState::Bndf2(a, b, filename);
-            }
-            ParserState::Bndf2(a, b, filename) => {
-                let line = line.strip_prefix(' ').unwrap();
-                let long_name = line.trim().to_string();
-                state = ParserState::Bndf3(a, b, filename, long_name);
-            }
-            ParserState::Bndf3(a, b, filename, long_name) => {
-                let line = line.strip_prefix(' ').unwrap();
-                let short_name = line.trim().to_string();
-                state = ParserState::Bndf4(a, b, filename, long_name, short_name);
+        }
+        ParserState::Device1 => {
+            let line = line.trim();
+            let mut values = line.split('%');
+            let name = values.next().ok_or(ParseLineError::MissingValue)?;
+            let quantity = values.next().ok_or(ParseLineError::MissingValue)?;
+            state = ParserState::Device2(name.trim().to_string(), quantity.trim().to_string());
+        }
+        ParserState::Device2(name, quantity) => {
+            // The PROP ID follows the last '%' and may contain spaces.
+            let (line, prop_id) = line.rsplit_once('%').ok_or(ParseLineError::MissingValue)?;
+            let mut values = line.split_whitespace();
+            let x1 = next_value(&mut values)?;
+            let y1 = next_value(&mut values)?;
+            let z1 = next_value(&mut values)?;
+            let x2 = next_value(&mut values)?;
+            let y2 = next_value(&mut values)?;
+            let z2 = next_value(&mut values)?;
+            let state0: i32 = next_value(&mut values)?;
+            let nparams: i32 = next_value(&mut values)?;
+            let ps = if values.next() == Some("#") {
+                let x1n = next_value(&mut values)?;
+                let y1n = next_value(&mut values)?;
+                let z1n = next_value(&mut values)?;
+                let x2n = next_value(&mut values)?;
+                let y2n = next_value(&mut values)?;
+                let z2n = next_value(&mut values)?;
+                Some((Xyz::new(x1n, y1n, z1n), Xyz::new(x2n, y2n, z2n)))
+            } else {
+                None
+            };
+            let device = SmvDevice {
+                name,
+                quantity,
+                p1: Xyz::new(x1, y1, z1),
+                p2: Xyz::new(x2, y2, z2),
+                ps,
+                prop_id: prop_id.trim().to_string(),
+                prop_index: None,
+                state0,
+                nparams,
+            };
+            pending_file.devcs.push(device);
+            state = ParserState::None;
+        }
+
+        //             DEVICE
+        //  AOVVFlow % VOLUME FLOW
+        //     31.80000    13.00000    13.80000     0.00000     0.00000    -1.00000  0  0 #     31.30000    12.50000    13.80000    32.30000    13.50000    13.80000 % null
+        ParserState::Offset => {
+            let line = line.trim();
+            let mut values = line.split_whitespace();
+            let x = next_value(&mut values)?;
+            let y = next_value(&mut values)?;
+            let z = next_value(&mut values)?;
+            pending_file.offsets.push(Xyz { x, y, z });
+            state = ParserState::None;
+        }
+        ParserState::Pdim => {
+            let line = line.trim();
+            let mut values = line.split_whitespace();
+            let xbar0 = next_value(&mut values)?;
+            let xbar = next_value(&mut values)?;
+            let ybar0 = next_value(&mut values)?;
+            let ybar = next_value(&mut values)?;
+            let zbar0 = next_value(&mut values)?;
+            let zbar = next_value(&mut values)?;
+            let r = next_value(&mut values)?;
+            let g = next_value(&mut values)?;
+            let b = next_value(&mut values)?;
+            pending_file.pdims.push(PdimBlock {
+                xbar0,
+                xbar,
+                ybar0,
+                ybar,
+                zbar0,
+                zbar,
+                color: Rgbf { r, g, b },
+            });
+            state = ParserState::None;
+        }
+        ParserState::Vent1 => {
+            let mut values = line.split_whitespace();
+            let total_vents: usize = next_value(&mut values)?;
+            let n_dummy_vents: usize = next_value(&mut values)?;
+            let n_vents = total_vents
+                .checked_sub(n_dummy_vents)
+                .ok_or(ParseLineError::InvalidValue("more dummy vents than vents"))?;
+            let first_vents = Vec::with_capacity(n_vents);
+            let first_dummy_vents = Vec::with_capacity(n_dummy_vents);
+            state = ParserState::Vent2(n_vents, first_vents, n_dummy_vents, first_dummy_vents);
+        }
+        ParserState::Vent2(n_vents, mut first_vents, n_dummy_vents, mut first_dummy_vents) => {
+            let f = line.trim().parse()?;
+            if first_vents.len() < n_vents {
+                first_vents.push(f);
+            } else if first_dummy_vents.len() < n_dummy_vents {
+                first_dummy_vents.push(f);
+            }
+            if (first_vents.len() < n_vents) || first_dummy_vents.len() < n_dummy_vents {
+                state = ParserState::Vent2(n_vents, first_vents, n_dummy_vents, first_dummy_vents);
+            } else {
+                let second_vents = Vec::with_capacity(n_vents);
+                let second_dummy_vents = Vec::with_capacity(n_dummy_vents);
+                state = ParserState::Vent3(
+                    n_vents,
+                    first_vents,
+                    second_vents,
+                    n_dummy_vents,
+                    first_dummy_vents,
+                    second_dummy_vents,
+                );
             }
-            ParserState::Bndf4(cell_centred, vs, filename, long_name, short_name) => {
-                let line = line.strip_prefix(' ').unwrap();
-                let units = line.trim().to_string();
-                state =
-                    ParserState::Bndf5(cell_centred, vs, filename, long_name, short_name, units);
+        }
+        ParserState::Vent3(
+            n_vents,
+            mut first_vents,
+            mut second_vents,
+            n_dummy_vents,
+            mut first_dummy_vents,
+            mut second_dummy_vents,
+        ) => {
+            let f = line.trim().parse()?;
+            if second_vents.len() < n_vents {
+                second_vents.push(f);
+            } else if second_dummy_vents.len() < n_dummy_vents {
+                second_dummy_vents.push(f);
+            }
+            if (second_vents.len() < n_vents) || second_dummy_vents.len() < n_dummy_vents {
+                state = ParserState::Vent3(
+                    n_vents,
+                    first_vents,
+                    second_vents,
+                    n_dummy_vents,
+                    first_dummy_vents,
+                    second_dummy_vents,
+                );
+            } else {
+                // Normal and dummy vents are saved together, the dummy vents
+                // are flagged and always follow the normal vents.
+                first_vents.append(&mut first_dummy_vents);
+                second_vents.append(&mut second_dummy_vents);
+                let mut vents = Vec::with_capacity(n_vents + n_dummy_vents);
+                for (i, (first, second)) in first_vents.into_iter().zip(second_vents).enumerate() {
+                    let mut vent = SmvVent::new(first, second);
+                    vent.dummy = i >= n_vents;
+                    vents.push(vent);
+                }
+                pending_file.vents.push(vents);
+                state = ParserState::None;
             }
-            ParserState::Bndf5(a, b, filename, long_name, short_name, units) => {
-                pending_file.bndfs.push(Bndf {
-                    a,
-                    b,
+        }
+        ParserState::Grid(name) => {
+            let line = line.trim();
+            let mut values = line.split_whitespace();
+            let i_bar = next_value(&mut values)?;
+            let j_bar = next_value(&mut values)?;
+            let k_bar = next_value(&mut values)?;
+            let mesh_type = next_value(&mut values)?;
+            pending_file.grids.push(GridBlock {
+                name,
+                i_bar,
+                j_bar,
+                k_bar,
+                mesh_type,
+            });
+            state = ParserState::None;
+        }
+        ParserState::Smoke3d1(smoke_type, mesh) => {
+            let file_name = strip_indent(line)?.trim().to_string();
+            state = ParserState::Smoke3d2(smoke_type, mesh, file_name);
+        }
+        ParserState::Smoke3d2(smoke_type, mesh, file_name) => {
+            let long_name = strip_indent(line)?.trim().to_string();
+            state = ParserState::Smoke3d3(smoke_type, mesh, file_name, long_name);
+        }
+        ParserState::Smoke3d3(smoke_type, mesh, file_name, long_name) => {
+            let short_name = strip_indent(line)?.trim().to_string();
+            state = ParserState::Smoke3d4(smoke_type, mesh, file_name, long_name, short_name);
+        }
+        ParserState::Smoke3d4(smoke_type, mesh, file_name, long_name, short_name) => {
+            let units = strip_indent(line)?.trim().to_string();
+            pending_file.smoke_3d.push(Smoke3d {
+                smoke_type,
+                file_name,
+                mesh,
+                long_name,
+                short_name,
+                units,
+            });
+            state = ParserState::None;
+        }
+        ParserState::Isof1(iso_type, mesh) => {
+            let filename = strip_indent(line)?.trim().to_string();
+            state = ParserState::Isof2(iso_type, mesh, filename);
+        }
+        ParserState::Isof2(iso_type, mesh, filename) => {
+            let long_name = strip_indent(line)?.trim().to_string();
+            state = ParserState::Isof3(iso_type, mesh, filename, long_name);
+        }
+        ParserState::Isof3(iso_type, mesh, filename, long_name) => {
+            let short_name = strip_indent(line)?.trim().to_string();
+            state = ParserState::Isof4(iso_type, mesh, filename, long_name, short_name);
+        }
+        ParserState::Isof4(iso_type, mesh, filename, long_name, short_name) => {
+            let units = strip_indent(line)?.trim().to_string();
+            pending_file.isofs.push(Isof {
+                iso_type,
+                mesh,
+                filename,
+                long_name,
+                short_name,
+                units,
+            });
+            state = ParserState::None;
+        }
+        ParserState::Slcf1(cell_centred, header) => {
+            let line = strip_indent(line)?;
+            let filename = line.trim().to_string();
+            state = ParserState::Slcf2(cell_centred, header, filename);
+        }
+        ParserState::Slcf2(cell_centred, header, filename) => {
+            let line = strip_indent(line)?;
+            let long_name = line.trim().to_string();
+            state = ParserState::Slcf3(cell_centred, header, filename, long_name);
+        }
+        ParserState::Slcf3(cell_centred, header, filename, long_name) => {
+            let line = strip_indent(line)?;
+            let short_name = line.trim().to_string();
+            state = ParserState::Slcf4(cell_centred, header, filename, long_name, short_name);
+        }
+        ParserState::Slcf4(cell_centred, header, filename, long_name, short_name) => {
+            let line = strip_indent(line)?;
+            let units = line.trim().to_string();
+            pending_file.slcfs.push(Slcf {
+                cell_centred,
+                mesh: header.mesh,
+                slice_type: header.slice_type,
+                id: header.id,
+                ijk: header.ijk,
+                index: header.index,
+                filename,
+                long_name,
+                short_name,
+                units,
+            });
+            state = ParserState::None;
+        }
+        ParserState::Bndf1(a, b) => {
+            let line = strip_indent(line)?;
+            let filename = line.trim().to_string();
+            state = ParserState::Bndf2(a, b, filename);
+        }
+        ParserState::Bndf2(a, b, filename) => {
+            let line = strip_indent(line)?;
+            let long_name = line.trim().to_string();
+            state = ParserState::Bndf3(a, b, filename, long_name);
+        }
+        ParserState::Bndf3(a, b, filename, long_name) => {
+            let line = strip_indent(line)?;
+            let short_name = line.trim().to_string();
+            state = ParserState::Bndf4(a, b, filename, long_name, short_name);
+        }
+        ParserState::Bndf4(a, b, filename, long_name, short_name) => {
+            let line = strip_indent(line)?;
+            let units = line.trim().to_string();
+            pending_file.bndfs.push(Bndf {
+                mesh: a,
+                b,
+                filename,
+                long_name,
+                short_name,
+                units,
+            });
+            state = ParserState::None;
+        }
+        ParserState::Prt51(n) => {
+            let line = strip_indent(line)?;
+            let filename = line.trim().to_string();
+            state = ParserState::Prt52(n, filename);
+        }
+        ParserState::Prt52(mesh, filename) => {
+            // This is the number of particle classes
+            let n: usize = line.trim().parse()?;
+            let classes = Vec::with_capacity(n);
+            if n > 0 {
+                state = ParserState::Prt53(mesh, filename, n, classes);
+            } else {
+                pending_file.prt5s.push(Prt5 {
+                    mesh,
                     filename,
-                    long_name,
-                    short_name,
-                    units,
+                    classes,
                 });
                 state = ParserState::None;
             }
-            ParserState::Prt51(n) => {
-                let line = line.strip_prefix(' ').unwrap();
-                let filename = line.trim().to_string();
-                state = ParserState::Prt52(n, filename);
-            }
-            ParserState::Prt52(n, filename) => {
-                let line = line.strip_prefix(' ').unwrap();
-                let a: i64 = line.trim().parse().unwrap();
-                state = ParserState::Prt53(n, filename, a);
-            }
-            ParserState::Prt53(n, filename, a) => {
-                let line = line.strip_prefix(' ').unwrap();
-                let b: i64 = line.trim().parse().unwrap();
-                pending_file.prt5s.push(Prt5 { n, filename, a, b });
-                state = ParserState::None;
-            }
-            ParserState::DeviceAct(name) => {
-                let mut values = line.trim().split_whitespace();
-                let i = values.next().unwrap().parse().unwrap();
-                let v: f64 = values.next().unwrap().parse().unwrap();
-                let n = values.next().unwrap().parse().unwrap();
-                pending_file
-                    .device_acts
-                    .push(SmvDeviceAct { name, n, v, i });
-                state = ParserState::None;
-            }
-            ParserState::Endf => {
-                let line = line.strip_prefix(' ').unwrap();
-                pending_file.endf_filename = Some(line.trim().to_string());
-                state = ParserState::None;
-            }
-            ParserState::SurfDef => {
-                let line = line.strip_prefix(' ').unwrap();
-                pending_file.surf_def = Some(line.trim().to_string());
-                state = ParserState::None;
-            }
-            ParserState::Xyz => {
-                let line = line.strip_prefix(' ').unwrap();
-                pending_file.xyzs.push(line.parse()?);
-                state = ParserState::None;
-            }
-            ParserState::ChidBlock => {
-                // This line is the CHID
-                let line = line.strip_prefix(' ').unwrap();
-                pending_file.chid = Some(line.parse()?);
-                state = ParserState::None;
-            }
-            ParserState::SolidHt3d => {
-                let line = line.strip_prefix(' ').unwrap();
-                pending_file.solid_ht3d = Some(line.trim().parse()?);
-                state = ParserState::None;
-            }
-            ParserState::CsvfBlock1 => {
-                state = ParserState::CsvfBlock2(line.trim().to_string());
-            }
-            ParserState::CsvfBlock2(ref csv_type) => {
-                pending_file.csvfs.push(CSVEntry {
-                    type_: csv_type.clone(),
-                    filename: line.trim().to_string(),
+        }
+        ParserState::Prt53(mesh, filename, n, mut classes) => {
+            classes.push(line.trim().parse()?);
+            if classes.len() >= n {
+                pending_file.prt5s.push(Prt5 {
+                    mesh,
+                    filename,
+                    classes,
                 });
                 state = ParserState::None;
+            } else {
+                state = ParserState::Prt53(mesh, filename, n, classes);
             }
-            ParserState::InpfBlock => {
-                // This line is the input filename
-                pending_file.input_filename = Some(line.trim().to_string());
-                state = ParserState::None;
-            }
-            ParserState::ObstBlock1 => {
-                // This the number of obsts
-                let n: usize = line.trim().parse().unwrap();
-                let first_obsts = Vec::with_capacity(n);
+        }
+        ParserState::DeviceAct(name) => {
+            let mut values = line.trim().split_whitespace();
+            let i = next_value(&mut values)?;
+            let v: f64 = next_value(&mut values)?;
+            let n = next_value(&mut values)?;
+            pending_file
+                .device_acts
+                .push(SmvDeviceAct { name, n, v, i });
+            state = ParserState::None;
+        }
+        ParserState::Endf => {
+            let line = strip_indent(line)?;
+            pending_file.endf_filename = Some(line.trim().to_string());
+            state = ParserState::None;
+        }
+        ParserState::SurfDef => {
+            let line = strip_indent(line)?;
+            pending_file.surf_def = Some(line.trim().to_string());
+            state = ParserState::None;
+        }
+        ParserState::Xyz => {
+            let line = strip_indent(line)?;
+            pending_file.xyzs.push(line.parse()?);
+            state = ParserState::None;
+        }
+        ParserState::ChidBlock => {
+            // This line is the CHID
+            let line = strip_indent(line)?;
+            let chid = line
+                .parse()
+                .map_err(|err| ParseLineError::InvalidChid(format!("{:?}", err)))?;
+            pending_file.chid = Some(chid);
+            state = ParserState::None;
+        }
+        ParserState::SolidHt3d => {
+            let line = strip_indent(line)?;
+            pending_file.solid_ht3d = Some(line.trim().parse()?);
+            state = ParserState::None;
+        }
+        ParserState::CsvfBlock1 => {
+            state = ParserState::CsvfBlock2(line.trim().to_string());
+        }
+        ParserState::CsvfBlock2(ref csv_type) => {
+            pending_file.csvfs.push(CSVEntry {
+                type_: csv_type.clone(),
+                filename: line.trim().to_string(),
+            });
+            state = ParserState::None;
+        }
+        ParserState::InpfBlock => {
+            // This line is the input filename
+            pending_file.input_filename = Some(line.trim().to_string());
+            state = ParserState::None;
+        }
+        ParserState::ObstBlock1 => {
+            // This the number of obsts
+            let n: usize = line.trim().parse()?;
+            let first_obsts = Vec::with_capacity(n);
+            state = ParserState::ObstBlock2(n, first_obsts);
+        }
+        ParserState::ObstBlock2(n, mut first_obsts) => {
+            let f = line.trim().parse()?;
+            first_obsts.push(f);
+            if first_obsts.len() >= n {
+                let second_obsts = Vec::with_capacity(n);
+                state = ParserState::ObstBlock3(n, first_obsts, second_obsts);
+            } else {
                 state = ParserState::ObstBlock2(n, first_obsts);
             }
-            ParserState::ObstBlock2(n, mut first_obsts) => {
-                let f = line.trim().parse().unwrap();
-                first_obsts.push(f);
-                if first_obsts.len() >= n {
-                    let second_obsts = Vec::with_capacity(n);
-                    state = ParserState::ObstBlock3(n, first_obsts, second_obsts);
-                } else {
-                    state = ParserState::ObstBlock2(n, first_obsts);
-                }
-            }
-            ParserState::ObstBlock3(n, first_obsts, mut second_obsts) => {
-                let f = line.trim().parse().unwrap();
-                second_obsts.push(f);
-                if second_obsts.len() >= n {
-                    let mut obsts = Vec::with_capacity(n);
-                    for (half1, half2) in first_obsts.into_iter().zip(second_obsts.into_iter()) {
-                        obsts.push(SmvObst::new(half1, half2));
-                    }
-                    pending_file.obsts.push(obsts);
-                    state = ParserState::None
-                } else {
-                    state = ParserState::ObstBlock3(n, first_obsts, second_obsts);
+        }
+        ParserState::ObstBlock3(n, first_obsts, mut second_obsts) => {
+            let f = line.trim().parse()?;
+            second_obsts.push(f);
+            if second_obsts.len() >= n {
+                let mut obsts = Vec::with_capacity(n);
+                for (half1, half2) in first_obsts.into_iter().zip(second_obsts.into_iter()) {
+                    obsts.push(SmvObst::new(half1, half2));
                 }
+                pending_file.obsts.push(obsts);
+                state = ParserState::None
+            } else {
+                state = ParserState::ObstBlock3(n, first_obsts, second_obsts);
             }
-            ParserState::Surface1 => {
-                let line = line.strip_prefix(' ').unwrap();
-                let name = line.trim().parse().unwrap();
-                state = ParserState::Surface2(name);
-            }
-            ParserState::Surface2(name) => {
-                let line = line.trim();
-                let mut values = line.split_whitespace();
-                let ignition_temperature = values.next().unwrap().parse().unwrap();
-                let emissivity = values.next().unwrap().parse().unwrap();
-                state = ParserState::Surface3(name, ignition_temperature, emissivity);
-            }
-            ParserState::Surface3(name, ignition_temperature, emissivity) => {
-                let line = line.trim();
-                let mut values = line.split_whitespace();
-                let s_type = values.next().unwrap().parse().unwrap();
-                let t_width = values.next().unwrap().parse().unwrap();
-                let t_height = values.next().unwrap().parse().unwrap();
-                let r = values.next().unwrap().parse().unwrap();
-                let g = values.next().unwrap().parse().unwrap();
-                let b = values.next().unwrap().parse().unwrap();
-                let a = values.next().unwrap().parse().unwrap();
-                let color = Rgbaf::new(r, g, b, a);
-                state = ParserState::Surface4(
-                    name,
-                    ignition_temperature,
-                    emissivity,
-                    s_type,
-                    t_width,
-                    t_height,
-                    color,
-                );
-            }
-            ParserState::Surface4(
+        }
+        ParserState::Surface1 => {
+            let line = strip_indent(line)?;
+            let name = line.trim().parse()?;
+            state = ParserState::Surface2(name);
+        }
+        ParserState::Surface2(name) => {
+            let line = line.trim();
+            let mut values = line.split_whitespace();
+            let ignition_temperature = next_value(&mut values)?;
+            let emissivity = next_value(&mut values)?;
+            state = ParserState::Surface3(name, ignition_temperature, emissivity);
+        }
+        ParserState::Surface3(name, ignition_temperature, emissivity) => {
+            let line = line.trim();
+            let mut values = line.split_whitespace();
+            let s_type = next_value(&mut values)?;
+            let t_width = next_value(&mut values)?;
+            let t_height = next_value(&mut values)?;
+            let r = next_value(&mut values)?;
+            let g = next_value(&mut values)?;
+            let b = next_value(&mut values)?;
+            let a = next_value(&mut values)?;
+            let color = Rgbaf::new(r, g, b, a);
+            state = ParserState::Surface4(
+                name,
+                ignition_temperature,
+                emissivity,
+                s_type,
+                t_width,
+                t_height,
+                color,
+            );
+        }
+        ParserState::Surface4(
+            name,
+            ignition_temperature,
+            emissivity,
+            surface_type,
+            t_width,
+            t_height,
+            color,
+        ) => {
+            let line = strip_indent(line)?;
+            let line = line.trim();
+            let texture_file = line.to_string();
+            let surface = SmvSurface {
                 name,
                 ignition_temperature,
                 emissivity,
@@ -1545,40 +2165,61 @@ pub fn parse_smv_file<R: Read>(input: R) -> Result<SmvFile, Box<dyn std::error::
                 t_width,
                 t_height,
                 color,
-            ) => {
-                let line = line.strip_prefix(' ').unwrap();
-                let line = line.trim();
-                let texture_file = line.to_string();
-                let surface = SmvSurface {
-                    name,
-                    ignition_temperature,
-                    emissivity,
-                    surface_type,
-                    t_width,
-                    t_height,
-                    color,
-                    texture_file,
-                };
-                pending_file.surfs.push(surface);
-                state = ParserState::None;
-            }
-            ParserState::Trn1(axis) => {
-                let skip_n: usize = line.trim().parse().unwrap();
-                let entries = Vec::new();
-                state = ParserState::Trn2(axis, skip_n, entries);
+                texture_file,
+            };
+            pending_file.surfs.push(surface);
+            state = ParserState::None;
+        }
+        ParserState::Trn1(axis) => {
+            let skip_n: usize = line.trim().parse()?;
+            let entries = Vec::new();
+            state = ParserState::Trn2(axis, skip_n, entries);
+        }
+        ParserState::Trn2(_axis, ref mut skip_n, ref mut entries) => {
+            if *skip_n > 0 {
+                // TODO: this is mimicking smv source code, but not sure why
+                *skip_n -= 1;
+            } else {
+                let f: TrnEntry = line.trim().parse()?;
+                entries.push(f);
             }
-            ParserState::Trn2(_axis, ref mut skip_n, ref mut entries) => {
-                if *skip_n > 0 {
-                    // TODO: this is mimicking smv source code, but not sure why
-                    *skip_n -= 1;
-                    continue;
+        }
+    }
+    Ok(state)
+}
+
+/// A RAMP, a named function of time defined by (time, value) points.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct SmvRamp {
+    pub name: String,
+    pub points: Vec<(f64, f64)>,
+}
+
+impl SmvRamp {
+    /// Linearly interpolate the value of the ramp at time `t`. As in FDS, the
+    /// value is held constant before the first and after the last point.
+    /// Returns None if the ramp has no points.
+    pub fn value_at(&self, t: f64) -> Option<f64> {
+        let (t_first, v_first) = *self.points.first()?;
+        let (t_last, v_last) = *self.points.last()?;
+        if t <= t_first {
+            return Some(v_first);
+        }
+        if t >= t_last {
+            return Some(v_last);
+        }
+        for pair in self.points.windows(2) {
+            let (t1, v1) = pair[0];
+            let (t2, v2) = pair[1];
+            if t <= t2 {
+                if t2 <= t1 {
+                    return Some(v2);
                 }
-                let f = line.trim().parse().unwrap();
-                entries.push(f);
+                return Some(v1 + (v2 - v1) * (t - t1) / (t2 - t1));
             }
         }
+        Some(v_last)
     }
-    Ok(pending_file.try_into()?)
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -1619,4 +2260,177 @@ mod tests {
         assert_eq!(result.meshes[0].trny.len(), 19);
         assert_eq!(result.meshes[0].trnz.len(), 26);
     }
+    #[test]
+    fn parse_smv_slcf_header() {
+        let result = parse_smv_file(std::io::Cursor::new(include_str!("test1.smv")))
+            .expect("smv parsing failed");
+        assert_eq!(result.slcfs.len(), 95);
+        let slcf = &result.slcfs[0];
+        assert_eq!(slcf.filename, "abcde_0001_01.sf");
+        assert!(!slcf.cell_centred);
+        assert_eq!(slcf.mesh, 1);
+        assert_eq!(slcf.slice_type, SliceType::Structured);
+        assert_eq!(slcf.ijk, GridRegion::new(226, 226, 0, 18, 0, 25));
+        assert_eq!(slcf.index, Some(1));
+        assert_eq!(slcf.plane(), Some(Axis::X));
+        let xb = result.slice_xb(slcf).expect("slice outside mesh");
+        assert_eq!(xb.x1, 31.9);
+        assert_eq!(xb.x1, xb.x2);
+        assert_eq!((xb.y1, xb.y2), (7.3, 9.1));
+        assert_eq!((xb.z1, xb.z2), (3.8, 6.3));
+    }
+    #[test]
+    fn parse_slcf_header_with_id() {
+        let header: SlcfHeader = "3 # UNSTRUCTURED % TEMP_1 & 0 10 0 10 5 5".parse().unwrap();
+        assert_eq!(header.mesh, 3);
+        assert_eq!(header.slice_type, SliceType::Unstructured);
+        assert_eq!(header.id.as_deref(), Some("TEMP_1"));
+        assert_eq!(header.ijk, GridRegion::new(0, 10, 0, 10, 5, 5));
+        assert_eq!(header.index, None);
+    }
+    #[test]
+    fn parse_smv_props() {
+        let result = parse_smv_file(std::io::Cursor::new(include_str!("test1.smv")))
+            .expect("smv parsing failed");
+        assert_eq!(result.props.len(), 3);
+        assert_eq!(result.props[2].name, "Cleary Photoelectric P1");
+        assert_eq!(result.props[2].smokeview_ids, vec!["smoke_detector"]);
+        let sprinkler = result.device("SPRK").expect("no device");
+        assert_eq!(sprinkler.quantity(), "SPRINKLER LINK TEMPERATURE");
+        assert_eq!(sprinkler.xyz(), Xyz::new(34.5, 3.0, 6.29));
+        assert_eq!(
+            sprinkler.prop_id(),
+            "Generic Residential Link01_Generic Residential Spray01"
+        );
+        let prop = result.device_prop(sprinkler).expect("no prop");
+        assert_eq!(prop.smokeview_ids, vec!["sprinkler_pendent"]);
+        let detectors: Vec<&str> = result
+            .devices_with_smokeview_id("smoke_detector")
+            .map(|devc| devc.name())
+            .collect();
+        assert_eq!(detectors, vec!["SmokeDetector1"]);
+    }
+    #[test]
+    fn parse_smv_smoke_3d() {
+        let result = parse_smv_file(std::io::Cursor::new(include_str!("room_fire.smv")))
+            .expect("smv parsing failed");
+        assert_eq!(result.smoke_3d.len(), 4);
+        assert_eq!(
+            result.smoke_3d[2],
+            Smoke3d {
+                smoke_type: Smoke3dType::G,
+                mesh: 1,
+                file_name: "room_fire_0001_03.s3d".to_string(),
+                long_name: "TEMPERATURE".to_string(),
+                short_name: "temp".to_string(),
+                units: "C".to_string(),
+            }
+        );
+    }
+    #[test]
+    fn parse_smv_isof() {
+        let input = format!(
+            "{}ISOF     1\n room_fire_0001_01.iso\n TEMPERATURE\n temp\n C\n",
+            include_str!("room_fire.smv")
+        );
+        let result = parse_smv_file(std::io::Cursor::new(input)).expect("smv parsing failed");
+        assert_eq!(
+            result.isofs,
+            vec![Isof {
+                iso_type: IsoType::Structured,
+                mesh: 1,
+                filename: "room_fire_0001_01.iso".to_string(),
+                long_name: "TEMPERATURE".to_string(),
+                short_name: "temp".to_string(),
+                units: "C".to_string(),
+            }]
+        );
+    }
+    #[test]
+    fn parse_smv_pl3d() {
+        let result = parse_smv_file(std::io::Cursor::new(include_str!("test1.smv")))
+            .expect("smv parsing failed");
+        assert_eq!(result.pl3ds.len(), 360);
+        let pl3d = &result.pl3ds[1];
+        assert_eq!(pl3d.time, 10.03);
+        assert_eq!(pl3d.mesh, 2);
+        assert_eq!(pl3d.filename, "abcde_0002_00000010_03.q");
+        assert_eq!(pl3d.quantities.len(), PL3D_QUANTITIES);
+        assert_eq!(
+            pl3d.quantities[4],
+            Pl3dQuantity {
+                long_name: "HRRPUV".to_string(),
+                short_name: "hrrpuv".to_string(),
+                units: "kW/m3".to_string(),
+            }
+        );
+    }
+    #[test]
+    fn parse_smv_ramps() {
+        let result = parse_smv_file(std::io::Cursor::new(include_str!("test1.smv")))
+            .expect("smv parsing failed");
+        assert_eq!(result.ramps.len(), 4);
+        assert_eq!(result.ramps[0].name, "RSRVD TEMPERATURE PROFILE");
+        let ramp = result.ramp("AptDoorOpen_RAMP").expect("no ramp");
+        assert_eq!(
+            ramp.points,
+            vec![(299.75, -1.0), (300.25, 1.0), (309.75, 1.0), (310.25, -1.0)]
+        );
+        assert_eq!(ramp.value_at(0.0), Some(-1.0));
+        assert_eq!(ramp.value_at(300.0), Some(0.0));
+        assert_eq!(ramp.value_at(305.0), Some(1.0));
+        assert_eq!(ramp.value_at(1000.0), Some(-1.0));
+    }
+    #[test]
+    fn parse_smv_truncated_line() {
+        // Cut the file off part way through the first OBST entry.
+        let input = include_str!("room_fire.smv");
+        let obst_start = input.find("\nOBST\n").unwrap();
+        let truncated = &input[..obst_start + 40];
+        let n_lines = truncated.lines().count();
+        let err = parse_smv_file(std::io::Cursor::new(truncated)).unwrap_err();
+        match err {
+            ParseSmvError::Line {
+                block,
+                line,
+                text,
+                cause,
+            } => {
+                assert_eq!(block, "OBST");
+                assert_eq!(line, n_lines);
+                assert_eq!(Some(text.as_str()), truncated.lines().last());
+                assert_eq!(cause, ParseLineError::MissingValue);
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+    #[test]
+    fn parse_smv_truncated_block() {
+        let input =
+            "TITLE\n test\n\nCHID\n test\n\nSLCF 1 # STRUCTURED & 0 1 0 1 0 0 ! 1\n test_01.sf\n";
+        let err = parse_smv_file(std::io::Cursor::new(input)).unwrap_err();
+        assert!(matches!(
+            err,
+            ParseSmvError::UnexpectedEof { ref block, line: 8 } if block == "SLCF"
+        ));
+    }
+    #[test]
+    fn parse_smv_bad_value() {
+        let input = "TITLE\n test\n\nALBEDO\n 0.3x\n";
+        let err = parse_smv_file(std::io::Cursor::new(input)).unwrap_err();
+        assert!(matches!(
+            err,
+            ParseSmvError::Line {
+                line: 5,
+                cause: ParseLineError::InvalidFloat(_),
+                ..
+            }
+        ));
+    }
+    #[test]
+    fn parse_smv_missing_chid() {
+        let input = "TITLE\n test\n\nINPF\n test.fds\n";
+        let err = parse_smv_file(std::io::Cursor::new(input)).unwrap_err();
+        assert!(matches!(err, ParseSmvError::MissingBlock("CHID")));
+    }
 }
@@ -0,0 +1,408 @@
+use crate::{IsoType, SliceType, Smoke3dType, SmvEvent, SmvFile, SmvMesh, Xb};
+use std::io::Write;
+
+impl SmvFile {
+    /// Write the SMV file in the text format read by [`crate::parse_smv_file`]
+    /// and Smokeview. Blocks which are not parsed (e.g. OUTLINE) are written
+    /// back unchanged, either before the meshes or after the mesh they
+    /// followed. Blank lines and the order of blocks are not kept.
+    pub fn write_to<W: Write>(&self, mut w: W) -> std::io::Result<()> {
+        writeln!(w, "TITLE")?;
+        writeln!(w, " {}", self.title.as_str())?;
+        writeln!(w)?;
+        if let Some(fds_version) = &self.fds_version {
+            writeln!(w, "FDSVERSION")?;
+            writeln!(w, "{}", fds_version)?;
+            writeln!(w)?;
+        }
+        if let Some(endf_filename) = &self.endf_filename {
+            writeln!(w, "ENDF")?;
+            writeln!(w, " {}", endf_filename)?;
+            writeln!(w)?;
+        }
+        writeln!(w, "INPF")?;
+        writeln!(w, " {}", self.input_filename)?;
+        writeln!(w)?;
+        writeln!(w, "CHID")?;
+        writeln!(w, " {}", self.chid.as_str())?;
+        writeln!(w)?;
+        if let Some(solid_ht3d) = self.solid_ht3d {
+            writeln!(w, "SOLID_HT3D")?;
+            writeln!(w, " {}", solid_ht3d)?;
+            writeln!(w)?;
+        }
+        for csvf in &self.csvfs {
+            writeln!(w, "CSVF")?;
+            writeln!(w, " {}", csvf.type_)?;
+            writeln!(w, " {}", csvf.filename)?;
+            writeln!(w)?;
+        }
+        writeln!(w, "NMESHES")?;
+        writeln!(w, " {}", self.meshes.len())?;
+        writeln!(w)?;
+        if let Some(view_times) = &self.view_times {
+            writeln!(w, "VIEWTIMES")?;
+            writeln!(
+                w,
+                " {} {} {}",
+                view_times.tour_tstart, view_times.tour_tstop, view_times.tour_ntimes
+            )?;
+            writeln!(w)?;
+        }
+        if let Some(albedo) = self.albedo {
+            writeln!(w, "ALBEDO")?;
+            writeln!(w, " {}", albedo)?;
+            writeln!(w)?;
+        }
+        if let Some(i_blank) = self.i_blank {
+            writeln!(w, "IBLANK")?;
+            writeln!(w, " {}", i_blank)?;
+            writeln!(w)?;
+        }
+        if let Some(gvec) = &self.gvec {
+            writeln!(w, "GVEC")?;
+            writeln!(w, " {} {} {}", gvec.x, gvec.y, gvec.z)?;
+            writeln!(w)?;
+        }
+        if let Some(surf_def) = &self.surf_def {
+            writeln!(w, "SURFDEF")?;
+            writeln!(w, " {}", surf_def)?;
+            writeln!(w)?;
+        }
+        for surf in &self.surfs {
+            writeln!(w, "SURFACE")?;
+            writeln!(w, " {}", surf.name)?;
+            writeln!(w, " {} {}", surf.ignition_temperature, surf.emissivity)?;
+            writeln!(
+                w,
+                " {} {} {} {} {} {} {}",
+                surf.surface_type,
+                surf.t_width,
+                surf.t_height,
+                surf.color.r,
+                surf.color.g,
+                surf.color.b,
+                surf.color.a
+            )?;
+            writeln!(w, " {}", surf.texture_file)?;
+            writeln!(w)?;
+        }
+        if let Some(texture_origin) = &self.texture_origin {
+            writeln!(w, "TOFFSET")?;
+            writeln!(
+                w,
+                " {} {} {}",
+                texture_origin.x, texture_origin.y, texture_origin.z
+            )?;
+            writeln!(w)?;
+        }
+        if !self.ramps.is_empty() {
+            writeln!(w, "RAMP")?;
+            writeln!(w, " {}", self.ramps.len())?;
+            for ramp in &self.ramps {
+                writeln!(w, " RAMP: {}", ramp.name)?;
+                writeln!(w, " {}", ramp.points.len())?;
+                for (t, v) in &ramp.points {
+                    writeln!(w, " {} {}", t, v)?;
+                }
+            }
+            writeln!(w)?;
+        }
+        for prop in &self.props {
+            writeln!(w, "PROP")?;
+            writeln!(w, " {}", prop.name)?;
+            writeln!(w, " {}", prop.smokeview_ids.len())?;
+            for smokeview_id in &prop.smokeview_ids {
+                writeln!(w, " {}", smokeview_id)?;
+            }
+            writeln!(w, " {}", prop.parameters.len())?;
+            for parameter in &prop.parameters {
+                writeln!(w, " {}", parameter)?;
+            }
+            writeln!(w)?;
+        }
+        for devc in &self.devcs {
+            writeln!(w, "DEVICE")?;
+            writeln!(w, " {} % {}", devc.name, devc.quantity)?;
+            write!(
+                w,
+                " {} {} {} {} {} {} {} {}",
+                devc.p1.x,
+                devc.p1.y,
+                devc.p1.z,
+                devc.p2.x,
+                devc.p2.y,
+                devc.p2.z,
+                devc.state0,
+                devc.nparams
+            )?;
+            if let Some((p1, p2)) = &devc.ps {
+                write!(
+                    w,
+                    " # {} {} {} {} {} {}",
+                    p1.x, p1.y, p1.z, p2.x, p2.y, p2.z
+                )?;
+            }
+            writeln!(w, " % {}", devc.prop_id)?;
+            writeln!(w)?;
+        }
+        self.write_unparsed_blocks(&mut w, 0)?;
+        for (n, mesh) in self.meshes.iter().enumerate() {
+            write_mesh(&mut w, mesh)?;
+            self.write_unparsed_blocks(&mut w, n + 1)?;
+        }
+        for xyz in &self.xyzs {
+            writeln!(w, "XYZ")?;
+            writeln!(w, " {}", xyz)?;
+        }
+        for slcf in &self.slcfs {
+            let name = if slcf.slice_type == SliceType::Terrain {
+                "SLCT"
+            } else if slcf.cell_centred {
+                "SLCC"
+            } else {
+                "SLCF"
+            };
+            write!(w, "{} {} # {}", name, slcf.mesh, slcf.slice_type.as_str())?;
+            if let Some(id) = &slcf.id {
+                write!(w, " % {}", id)?;
+            }
+            let ijk = &slcf.ijk;
+            write!(
+                w,
+                " & {} {} {} {} {} {}",
+                ijk.i1, ijk.i2, ijk.j1, ijk.j2, ijk.k1, ijk.k2
+            )?;
+            if let Some(index) = slcf.index {
+                write!(w, " ! {}", index)?;
+            }
+            writeln!(w)?;
+            writeln!(w, " {}", slcf.filename)?;
+            writeln!(w, " {}", slcf.long_name)?;
+            writeln!(w, " {}", slcf.short_name)?;
+            writeln!(w, " {}", slcf.units)?;
+        }
+        for smoke_3d in &self.smoke_3d {
+            let name = match smoke_3d.smoke_type {
+                Smoke3dType::F => "SMOKF3D",
+                Smoke3dType::G => "SMOKG3D",
+            };
+            writeln!(w, "{} {}", name, smoke_3d.mesh)?;
+            writeln!(w, " {}", smoke_3d.file_name)?;
+            writeln!(w, " {}", smoke_3d.long_name)?;
+            writeln!(w, " {}", smoke_3d.short_name)?;
+            writeln!(w, " {}", smoke_3d.units)?;
+        }
+        for isof in &self.isofs {
+            let name = match isof.iso_type {
+                IsoType::Structured => "ISOF",
+                IsoType::Geometry => "ISOG",
+            };
+            writeln!(w, "{} {}", name, isof.mesh)?;
+            writeln!(w, " {}", isof.filename)?;
+            writeln!(w, " {}", isof.long_name)?;
+            writeln!(w, " {}", isof.short_name)?;
+            writeln!(w, " {}", isof.units)?;
+        }
+        for pl3d in &self.pl3ds {
+            writeln!(w, "PL3D {} {}", pl3d.time, pl3d.mesh)?;
+            writeln!(w, " {}", pl3d.filename)?;
+            for quantity in &pl3d.quantities {
+                writeln!(w, " {}", quantity.long_name)?;
+                writeln!(w, " {}", quantity.short_name)?;
+                writeln!(w, " {}", quantity.units)?;
+            }
+        }
+        for bndf in &self.bndfs {
+            writeln!(w, "BNDF {} {}", bndf.mesh, bndf.b)?;
+            writeln!(w, " {}", bndf.filename)?;
+            writeln!(w, " {}", bndf.long_name)?;
+            writeln!(w, " {}", bndf.short_name)?;
+            writeln!(w, " {}", bndf.units)?;
+        }
+        for prt5 in &self.prt5s {
+            writeln!(w, "PRT5 {}", prt5.mesh)?;
+            writeln!(w, " {}", prt5.filename)?;
+            writeln!(w, " {}", prt5.classes.len())?;
+            for class in &prt5.classes {
+                writeln!(w, " {}", class)?;
+            }
+        }
+        for device_act in &self.device_acts {
+            writeln!(w, "DEVICE_ACT {}", device_act.name)?;
+            writeln!(w, " {} {} {}", device_act.i, device_act.v, device_act.n)?;
+        }
+        for event in &self.events {
+            let (name, n, i, t) = match *event {
+                SmvEvent::OpenVent { n, i, t } => ("OPEN_VENT", n, i, t),
+                SmvEvent::CloseVent { n, i, t } => ("CLOSE_VENT", n, i, t),
+                SmvEvent::ShowObst { n, i, t } => ("SHOW_OBST", n, i, t),
+                SmvEvent::HideObst { n, i, t } => ("HIDE_OBST", n, i, t),
+            };
+            writeln!(w, "{} {}", name, n)?;
+            writeln!(w, " {} {}", i, t)?;
+        }
+        Ok(())
+    }
+
+    /// Write the unparsed blocks which followed the given number of meshes.
+    fn write_unparsed_blocks<W: Write>(&self, w: &mut W, n_meshes: usize) -> std::io::Result<()> {
+        for block in &self.unparsed_blocks {
+            if block.n_meshes_before == n_meshes {
+                for line in &block.lines {
+                    writeln!(w, "{}", line)?;
+                }
+                writeln!(w)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_xb<W: Write>(w: &mut W, xb: &Xb) -> std::io::Result<()> {
+    write!(
+        w,
+        " {} {} {} {} {} {}",
+        xb.x1, xb.x2, xb.y1, xb.y2, xb.z1, xb.z2
+    )
+}
+
+fn write_mesh<W: Write>(w: &mut W, mesh: &SmvMesh) -> std::io::Result<()> {
+    writeln!(w, "OFFSET")?;
+    writeln!(w, " {} {} {}", mesh.offset.x, mesh.offset.y, mesh.offset.z)?;
+    writeln!(w)?;
+    writeln!(w, "GRID {}", mesh.name)?;
+    writeln!(
+        w,
+        " {} {} {} {}",
+        mesh.i_bar, mesh.j_bar, mesh.k_bar, mesh.mesh_type
+    )?;
+    writeln!(w)?;
+    writeln!(w, "PDIM")?;
+    write_xb(w, &mesh.dims)?;
+    writeln!(w, " {} {} {}", mesh.color.r, mesh.color.g, mesh.color.b)?;
+    writeln!(w)?;
+    for (name, trns) in [
+        ("TRNX", &mesh.trnx),
+        ("TRNY", &mesh.trny),
+        ("TRNZ", &mesh.trnz),
+    ]
+    .iter()
+    {
+        writeln!(w, "{}", name)?;
+        writeln!(w, " 0")?;
+        for trn in trns.iter() {
+            writeln!(w, " {} {}", trn.i, trn.f)?;
+        }
+        writeln!(w)?;
+    }
+    writeln!(w, "OBST")?;
+    writeln!(w, " {}", mesh.obsts.len())?;
+    for obst in &mesh.obsts {
+        write_xb(w, &obst.xb_exact)?;
+        let s = &obst.surfaces;
+        write!(
+            w,
+            " {} {} {} {} {} {} {}",
+            obst.id, s.min_x, s.max_x, s.min_y, s.max_y, s.min_z, s.max_z
+        )?;
+        if let Some(t) = &obst.texture_origin {
+            write!(w, " {} {} {}", t.x, t.y, t.z)?;
+        }
+        writeln!(w)?;
+    }
+    for obst in &mesh.obsts {
+        let ijk = &obst.ijk;
+        writeln!(
+            w,
+            " {} {} {} {} {} {} {} {}",
+            ijk.i1, ijk.i2, ijk.j1, ijk.j2, ijk.k1, ijk.k2, obst.colour_index, obst.block_type
+        )?;
+    }
+    writeln!(w)?;
+    // Dummy vents must follow the normal vents.
+    let vents: Vec<_> = mesh
+        .vents
+        .iter()
+        .filter(|vent| !vent.dummy)
+        .chain(mesh.vents.iter().filter(|vent| vent.dummy))
+        .collect();
+    let n_dummy_vents = mesh.vents.iter().filter(|vent| vent.dummy).count();
+    writeln!(w, "VENT")?;
+    writeln!(w, " {} {}", vents.len(), n_dummy_vents)?;
+    for vent in &vents {
+        write_xb(w, &vent.xb_exact)?;
+        write!(w, " {} {}", vent.vent_id, vent.s_num)?;
+        if let Some(t) = &vent.texture_origin {
+            write!(w, " {} {} {}", t.x, t.y, t.z)?;
+        }
+        writeln!(w)?;
+    }
+    for vent in &vents {
+        let ijk = &vent.ijk;
+        write!(
+            w,
+            " {} {} {} {} {} {} {} {}",
+            ijk.i1, ijk.i2, ijk.j1, ijk.j2, ijk.k1, ijk.k2, vent.vent_index, vent.vent_type
+        )?;
+        if let Some(c) = &vent.color {
+            write!(w, " {} {} {} {}", c.r, c.g, c.b, c.a)?;
+        }
+        writeln!(w)?;
+    }
+    writeln!(w)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_smv_file;
+
+    #[test]
+    fn write_smv_round_trip() {
+        for input in &[include_str!("room_fire.smv"), include_str!("test1.smv")] {
+            let smv = parse_smv_file(std::io::Cursor::new(input)).expect("smv parsing failed");
+            let mut buf = Vec::new();
+            smv.write_to(&mut buf).unwrap();
+            let reparsed =
+                parse_smv_file(std::io::Cursor::new(buf)).expect("written smv parsing failed");
+            assert_eq!(smv, reparsed);
+        }
+    }
+
+    #[test]
+    fn write_smv_unparsed_blocks() {
+        let input = include_str!("test1.smv");
+        let smv = parse_smv_file(std::io::Cursor::new(input)).expect("smv parsing failed");
+        let mut buf = Vec::new();
+        smv.write_to(&mut buf).unwrap();
+        let written = String::from_utf8(buf).unwrap();
+        for name in &[
+            "MATERIAL",
+            "CLASS_OF_PARTICLES",
+            "OUTLINE",
+            "HRRPUVCUT",
+            "CVENT",
+        ] {
+            let count = |text: &str| text.lines().filter(|line| line == name).count();
+            assert!(count(input) > 0);
+            assert_eq!(count(&written), count(input), "{}", name);
+        }
+        // Each CVENT block is written after the mesh it belongs to.
+        let order: Vec<_> = written
+            .lines()
+            .filter(|line| line.starts_with("GRID") || *line == "CVENT")
+            .collect();
+        assert_eq!(order.len(), 2 * smv.meshes.len());
+        for (pair, mesh) in order.chunks(2).zip(&smv.meshes) {
+            assert_eq!(pair, [format!("GRID {}", mesh.name).as_str(), "CVENT"]);
+        }
+        let outline = input
+            .lines()
+            .skip_while(|line| *line != "OUTLINE")
+            .take_while(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert!(written.contains(&outline));
+    }
+}
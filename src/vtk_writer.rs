@@ -0,0 +1,302 @@
+use crate::probe::value_positions;
+use crate::{Frame, ParseSliceError, SliceHeader, SliceParser, SmvMesh};
+use std::borrow::Borrow;
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+/// Writes the frames of a slice as VTK rectilinear grids, e.g. for viewing
+/// in ParaView. Values are written as point data. The values of cell-centred
+/// slices (SLCC) are placed at the centres of their cells, so both node and
+/// cell-centred slices are written on a grid with one point per value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VtkSliceWriter {
+    pub quantity: String,
+    pub units: String,
+    /// The coordinates of the grid points along each axis.
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+    pub z: Vec<f64>,
+}
+
+impl VtkSliceWriter {
+    /// Create a writer for a slice of the given mesh. Returns None if the
+    /// slice does not lie on the grid of the mesh.
+    pub fn new(mesh: &SmvMesh, header: &SliceHeader, cell_centred: bool) -> Option<Self> {
+        let [x, y, z] = value_positions(mesh, &header.dimensions.into(), cell_centred)?;
+        Some(Self {
+            quantity: header.quantity.clone(),
+            units: header.units.clone(),
+            x,
+            y,
+            z,
+        })
+    }
+
+    pub fn n_points(&self) -> usize {
+        self.x.len() * self.y.len() * self.z.len()
+    }
+
+    /// Write a frame as a VTK XML rectilinear grid (.vtr) file.
+    pub fn write_vtr<W: Write>(&self, mut w: W, frame: &Frame) -> std::io::Result<()> {
+        self.check_frame(frame)?;
+        let extent = format!(
+            "0 {} 0 {} 0 {}",
+            self.x.len() - 1,
+            self.y.len() - 1,
+            self.z.len() - 1
+        );
+        let name = escape_xml(&self.quantity);
+        writeln!(w, "<?xml version=\"1.0\"?>")?;
+        writeln!(
+            w,
+            "<VTKFile type=\"RectilinearGrid\" version=\"0.1\" byte_order=\"LittleEndian\">"
+        )?;
+        writeln!(w, "  <RectilinearGrid WholeExtent=\"{}\">", extent)?;
+        writeln!(w, "    <FieldData>")?;
+        writeln!(
+            w,
+            "      <DataArray type=\"Float32\" Name=\"TIME\" NumberOfTuples=\"1\" format=\"ascii\">{}</DataArray>",
+            frame.time
+        )?;
+        writeln!(w, "    </FieldData>")?;
+        writeln!(w, "    <Piece Extent=\"{}\">", extent)?;
+        writeln!(w, "      <PointData Scalars=\"{}\">", name)?;
+        write_data_array(&mut w, "Float32", &name, &frame.values)?;
+        writeln!(w, "      </PointData>")?;
+        writeln!(w, "      <Coordinates>")?;
+        write_data_array(&mut w, "Float64", "x", &self.x)?;
+        write_data_array(&mut w, "Float64", "y", &self.y)?;
+        write_data_array(&mut w, "Float64", "z", &self.z)?;
+        writeln!(w, "      </Coordinates>")?;
+        writeln!(w, "    </Piece>")?;
+        writeln!(w, "  </RectilinearGrid>")?;
+        writeln!(w, "</VTKFile>")?;
+        Ok(())
+    }
+
+    /// Write a frame as a legacy VTK (.vtk) file. Spaces in the quantity are
+    /// replaced with underscores as the legacy format does not allow them in
+    /// names.
+    pub fn write_legacy<W: Write>(&self, mut w: W, frame: &Frame) -> std::io::Result<()> {
+        self.check_frame(frame)?;
+        writeln!(w, "# vtk DataFile Version 3.0")?;
+        writeln!(w, "{} ({}) at {} s", self.quantity, self.units, frame.time)?;
+        writeln!(w, "ASCII")?;
+        writeln!(w, "DATASET RECTILINEAR_GRID")?;
+        writeln!(
+            w,
+            "DIMENSIONS {} {} {}",
+            self.x.len(),
+            self.y.len(),
+            self.z.len()
+        )?;
+        for (axis, coords) in [("X", &self.x), ("Y", &self.y), ("Z", &self.z)].iter() {
+            writeln!(w, "{}_COORDINATES {} double", axis, coords.len())?;
+            write_values(&mut w, coords)?;
+        }
+        writeln!(w, "POINT_DATA {}", self.n_points())?;
+        writeln!(w, "SCALARS {} float 1", self.quantity.replace(' ', "_"))?;
+        writeln!(w, "LOOKUP_TABLE default")?;
+        write_values(&mut w, &frame.values)?;
+        Ok(())
+    }
+
+    /// Write each frame to a .vtr file named `{stem}_{n}.vtr` in `dir`, along
+    /// with a `{stem}.pvd` collection which gives the time of each file.
+    /// Returns the path of the .pvd file.
+    pub fn write_series<P, I>(&self, dir: P, stem: &str, frames: I) -> std::io::Result<PathBuf>
+    where
+        P: AsRef<Path>,
+        I: IntoIterator,
+        I::Item: Borrow<Frame>,
+    {
+        self.write_frames(dir.as_ref(), stem, frames.into_iter().map(Ok), |err| err)
+    }
+
+    /// Write the frames of a slice file as for [`Self::write_series`], reading
+    /// each frame from the parser as it is written. Writing stops at the first
+    /// error reading a frame, which is returned. As for iterating over the
+    /// parser, an incomplete frame at the end of the file ends the series.
+    pub fn write_parser_series<P, R>(
+        &self,
+        dir: P,
+        stem: &str,
+        parser: &mut SliceParser<R>,
+    ) -> Result<PathBuf, ParseSliceError>
+    where
+        P: AsRef<Path>,
+        R: Read + Seek,
+    {
+        self.write_frames(dir.as_ref(), stem, parser, ParseSliceError::IOError)
+    }
+
+    fn write_frames<I, F, E>(
+        &self,
+        dir: &Path,
+        stem: &str,
+        frames: I,
+        io_error: fn(std::io::Error) -> E,
+    ) -> Result<PathBuf, E>
+    where
+        I: Iterator<Item = Result<F, E>>,
+        F: Borrow<Frame>,
+    {
+        let mut datasets = Vec::new();
+        for (n, frame) in frames.enumerate() {
+            let frame = frame?;
+            let frame = frame.borrow();
+            let filename = format!("{}_{:04}.vtr", stem, n);
+            write_file(&dir.join(&filename), |w| self.write_vtr(w, frame)).map_err(io_error)?;
+            datasets.push((frame.time, filename));
+        }
+        let path = dir.join(format!("{}.pvd", stem));
+        write_file(&path, |w| write_pvd(w, &datasets)).map_err(io_error)?;
+        Ok(path)
+    }
+
+    fn check_frame(&self, frame: &Frame) -> std::io::Result<()> {
+        if frame.values.len() == self.n_points() {
+            Ok(())
+        } else {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "slice frame has {} values but the grid has {} points",
+                    frame.values.len(),
+                    self.n_points()
+                ),
+            ))
+        }
+    }
+}
+
+fn write_file<F>(path: &Path, write: F) -> std::io::Result<()>
+where
+    F: FnOnce(&mut std::io::BufWriter<std::fs::File>) -> std::io::Result<()>,
+{
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    write(&mut file)?;
+    file.flush()
+}
+
+/// Write a ParaView data (.pvd) collection of files, each given with its
+/// time. Filenames are relative to the .pvd file.
+pub fn write_pvd<W: Write, S: AsRef<str>>(mut w: W, datasets: &[(f32, S)]) -> std::io::Result<()> {
+    writeln!(w, "<?xml version=\"1.0\"?>")?;
+    writeln!(
+        w,
+        "<VTKFile type=\"Collection\" version=\"0.1\" byte_order=\"LittleEndian\">"
+    )?;
+    writeln!(w, "  <Collection>")?;
+    for (time, filename) in datasets {
+        writeln!(
+            w,
+            "    <DataSet timestep=\"{}\" group=\"\" part=\"0\" file=\"{}\"/>",
+            time,
+            escape_xml(filename.as_ref())
+        )?;
+    }
+    writeln!(w, "  </Collection>")?;
+    writeln!(w, "</VTKFile>")?;
+    Ok(())
+}
+
+fn write_data_array<W: Write, T: std::fmt::Display>(
+    w: &mut W,
+    type_: &str,
+    name: &str,
+    values: &[T],
+) -> std::io::Result<()> {
+    writeln!(
+        w,
+        "        <DataArray type=\"{}\" Name=\"{}\" format=\"ascii\">",
+        type_, name
+    )?;
+    write_values(w, values)?;
+    writeln!(w, "        </DataArray>")?;
+    Ok(())
+}
+
+/// Write values separated by spaces, with a limited number on each line.
+fn write_values<W: Write, T: std::fmt::Display>(w: &mut W, values: &[T]) -> std::io::Result<()> {
+    for line in values.chunks(8) {
+        for (n, value) in line.iter().enumerate() {
+            if n > 0 {
+                write!(w, " ")?;
+            }
+            write!(w, "{}", value)?;
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_slice_file, parse_smv_file};
+
+    #[test]
+    fn write_room_fire_vtk() {
+        let smv = parse_smv_file(std::io::Cursor::new(include_str!("room_fire.smv"))).unwrap();
+        let slcf = &smv.slcfs[0];
+        let mesh = smv.slice_mesh(slcf).unwrap();
+        let slice =
+            parse_slice_file(&mut std::io::Cursor::new(include_bytes!("room_fire_01.sf"))).unwrap();
+        let writer = VtkSliceWriter::new(mesh, &slice.header, slcf.cell_centred).unwrap();
+        assert_eq!(
+            (writer.x.len(), writer.y.len(), writer.z.len()),
+            (1, 11, 25)
+        );
+        assert_eq!(writer.n_points(), slice.frames[0].values.len());
+
+        let mut legacy = Vec::new();
+        writer.write_legacy(&mut legacy, &slice.frames[0]).unwrap();
+        let legacy = String::from_utf8(legacy).unwrap();
+        assert!(legacy.contains("DIMENSIONS 1 11 25\n"));
+        assert!(legacy.contains("POINT_DATA 275\n"));
+
+        let dir = std::env::temp_dir().join(format!("red-smv-vtk-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pvd = writer
+            .write_series(&dir, "room_fire_01", &slice.frames)
+            .unwrap();
+        let pvd = std::fs::read_to_string(pvd).unwrap();
+        assert_eq!(pvd.matches("<DataSet ").count(), slice.frames.len());
+        let vtr = std::fs::read_to_string(dir.join("room_fire_01_0000.vtr")).unwrap();
+        assert!(vtr.contains("WholeExtent=\"0 0 0 10 0 24\""));
+
+        // Streaming from a parser gives the same series, and stops before an
+        // incomplete frame.
+        let data = include_bytes!("room_fire_01.sf");
+        let mut parser = SliceParser::new(std::io::Cursor::new(&data[..])).unwrap();
+        let streamed = writer
+            .write_parser_series(&dir, "streamed", &mut parser)
+            .unwrap();
+        let streamed = std::fs::read_to_string(streamed).unwrap();
+        assert_eq!(streamed, pvd.replace("room_fire_01_", "streamed_"));
+        let mut parser = SliceParser::new(std::io::Cursor::new(&data[..data.len() - 1])).unwrap();
+        let truncated = writer
+            .write_parser_series(&dir, "truncated", &mut parser)
+            .unwrap();
+        let truncated = std::fs::read_to_string(truncated).unwrap();
+        assert_eq!(
+            truncated.matches("<DataSet ").count(),
+            slice.frames.len() - 1
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let short = Frame {
+            time: 0.0,
+            values: vec![0.0; 3],
+        };
+        assert!(writer.write_vtr(Vec::new(), &short).is_err());
+    }
+}